@@ -0,0 +1,434 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::ErrorKind,
+    io::{Read, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt},
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::{Result, eyre};
+use sequoia_openpgp::{Cert, crypto::Password, parse::Parse, serialize::SerializeInto};
+use tracing::{debug, info, warn};
+
+use crate::{config::APP_CONFIG_DIR_NAME, gpg::Keyring};
+
+pub const AGENT_SOCKET_FILE_NAME: &str = "agent.sock";
+/// Overrides the location of the agent socket.
+pub const AGENT_SOCKET_ENV_VAR: &str = "ZPS_AGENT_SOCK";
+
+/// Upper bound for a single frame, well above any password entry.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// How long the agent waits on a silent client before dropping it.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const OP_DECRYPT: &[u8] = b"decrypt";
+const OP_CACHE: &[u8] = b"cache";
+const OP_LOCK: &[u8] = b"lock";
+const OP_STATUS: &[u8] = b"status";
+const OP_STOP: &[u8] = b"stop";
+
+const STATUS_OK: &[u8] = b"ok";
+const STATUS_LOCKED: &[u8] = b"locked";
+const STATUS_ERROR: &[u8] = b"error";
+
+/// Returns the agent socket path: `$ZPS_AGENT_SOCK`, else
+/// `$XDG_RUNTIME_DIR/zps/agent.sock`, else `<tmp>/zps-$UID/agent.sock`.
+/// Its folder must belong to the current user and be private to them.
+pub fn get_agent_socket_path() -> PathBuf {
+    if let Ok(path) = env::var(AGENT_SOCKET_ENV_VAR)
+        && !path.is_empty()
+    {
+        return PathBuf::from(path);
+    }
+    let dir = match env::var("XDG_RUNTIME_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join(APP_CONFIG_DIR_NAME),
+        _ => env::temp_dir().join(format!("{}-{}", APP_CONFIG_DIR_NAME, current_uid())),
+    };
+    dir.join(AGENT_SOCKET_FILE_NAME)
+}
+
+fn current_uid() -> u32 {
+    // SAFETY: getuid has no preconditions and cannot fail.
+    unsafe { libc::getuid() }
+}
+
+/// Checks that `dir` is a real directory owned by the current user and
+/// closed to everyone else, so nobody else can plant or replace the socket.
+fn check_socket_dir(dir: &Path) -> Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() {
+        return Err(eyre!("Agent socket folder {:?} is not a directory.", dir));
+    }
+    if metadata.uid() != current_uid() {
+        return Err(eyre!(
+            "Agent socket folder {:?} belongs to uid {}, not to you.",
+            dir,
+            metadata.uid()
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        return Err(eyre!(
+            "Agent socket folder {:?} has mode {:o}; it must be 0700.",
+            dir,
+            metadata.mode() & 0o777
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the uid of the process on the other end of `stream`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_uid(stream: &UnixStream) -> Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: `cred` and `len` are valid for writes and describe a ucred buffer.
+    let rc = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    if rc != 0 {
+        return Err(eyre!(
+            "Failed to read agent peer credentials: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(cred.uid)
+}
+
+/// Returns the uid of the process on the other end of `stream`.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_uid(stream: &UnixStream) -> Result<u32> {
+    let (mut uid, mut gid) = (0, 0);
+    // SAFETY: `uid` and `gid` are valid for writes.
+    if unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) } != 0 {
+        return Err(eyre!(
+            "Failed to read agent peer credentials: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(uid)
+}
+
+/// Fails unless the process on the other end of `stream` runs as the current user.
+fn check_peer(stream: &UnixStream) -> Result<()> {
+    let uid = peer_uid(stream)?;
+    if uid != current_uid() {
+        return Err(eyre!("Agent socket peer runs as uid {}, not as you.", uid));
+    }
+    Ok(())
+}
+
+/// Connects to the agent, or returns `None` if none is listening. The socket
+/// folder and the listening process must both belong to the current user.
+fn connect() -> Result<Option<UnixStream>> {
+    let socket_path = get_agent_socket_path();
+    let Some(dir) = socket_path.parent() else {
+        return Err(eyre!("Invalid agent socket path {:?}.", socket_path));
+    };
+    match fs::symlink_metadata(dir) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        _ => check_socket_dir(dir)?,
+    }
+    let stream = match UnixStream::connect(&socket_path) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(None),
+    };
+    check_peer(&stream)?;
+    Ok(Some(stream))
+}
+
+fn write_frame(stream: &mut UnixStream, data: &[u8]) -> Result<()> {
+    stream.write_all(&(data.len() as u32).to_be_bytes())?;
+    stream.write_all(data)?;
+    Ok(())
+}
+
+fn read_frame(stream: &mut UnixStream) -> Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(eyre!("Agent frame too large ({} bytes).", len));
+    }
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Sends a request to the agent and returns its status and payload,
+/// or `None` if no agent is listening.
+fn request(frames: &[&[u8]]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let Some(mut stream) = connect()? else {
+        return Ok(None);
+    };
+    write_frame(&mut stream, &(frames.len() as u32).to_be_bytes())?;
+    for frame in frames {
+        write_frame(&mut stream, frame)?;
+    }
+    let status = read_frame(&mut stream)?;
+    let payload = read_frame(&mut stream)?;
+    if status == STATUS_ERROR {
+        return Err(eyre!("Agent error: {}", String::from_utf8_lossy(&payload)));
+    }
+    Ok(Some((status, payload)))
+}
+
+/// Whether an agent is listening on the socket.
+pub fn agent_is_running() -> bool {
+    matches!(connect(), Ok(Some(_)))
+}
+
+/// Asks the agent to decrypt `ciphertext` with the key of `cert`.
+/// Returns `None` if no agent is running or it does not hold that key.
+pub fn agent_decrypt(cert: &Cert, ciphertext: &[u8]) -> Result<Option<Vec<u8>>> {
+    let fingerprint = cert.fingerprint().to_hex();
+    match request(&[OP_DECRYPT, fingerprint.as_bytes(), ciphertext])? {
+        Some((status, plaintext)) if status == STATUS_OK => Ok(Some(plaintext)),
+        _ => Ok(None),
+    }
+}
+
+/// Hands the TSK and its password to a running agent so later invocations
+/// can decrypt without prompting. Does nothing if no agent is running.
+pub fn agent_cache_key(cert: &Cert, password: Option<&Password>) -> Result<()> {
+    let tsk = cert.as_tsk().to_vec().map_err(|e| eyre!(e))?;
+    let password = password.map(|p| p.map(|p| p.to_vec())).unwrap_or_default();
+    request(&[OP_CACHE, &tsk, &password])?;
+    Ok(())
+}
+
+/// Wipes every key held by the agent. Returns whether an agent was running.
+pub fn agent_lock() -> Result<bool> {
+    Ok(request(&[OP_LOCK])?.is_some())
+}
+
+/// Asks the agent to exit. Returns whether an agent was running.
+pub fn agent_stop() -> Result<bool> {
+    Ok(request(&[OP_STOP])?.is_some())
+}
+
+/// Returns the agent's description of the keys it holds, or `None` if it is not running.
+pub fn agent_status() -> Result<Option<String>> {
+    Ok(request(&[OP_STATUS])?.map(|(_, payload)| String::from_utf8_lossy(&payload).into_owned()))
+}
+
+/// An unlocked key held by the agent.
+struct CachedKey {
+    keyring: Keyring,
+    unlocked_at: Instant,
+    last_used: Instant,
+}
+
+/// Keys held by the agent, by primary fingerprint.
+struct AgentState {
+    keys: HashMap<String, CachedKey>,
+    ttl: Duration,
+    idle_timeout: Duration,
+}
+
+impl AgentState {
+    /// Drops keys that outlived the TTL or have been idle for too long.
+    fn expire(&mut self) {
+        let (ttl, idle_timeout) = (self.ttl, self.idle_timeout);
+        self.keys.retain(|fingerprint, key| {
+            let keep = key.unlocked_at.elapsed() < ttl && key.last_used.elapsed() < idle_timeout;
+            if !keep {
+                info!("Key {} expired, wiping it.", fingerprint);
+            }
+            keep
+        });
+    }
+}
+
+/// Runs the agent in the foreground until it is stopped. Unlocked keys are
+/// wiped `ttl` after they were cached or after `idle_timeout` without use.
+pub fn run_agent(ttl: Duration, idle_timeout: Duration) -> Result<()> {
+    let socket_path = get_agent_socket_path();
+    if agent_is_running() {
+        return Err(eyre!("An agent is already listening on {:?}.", socket_path));
+    }
+    let dir = socket_path
+        .parent()
+        .ok_or_else(|| eyre!("Invalid agent socket path {:?}.", socket_path))?;
+    if let Some(parent) = dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => return Err(e.into()),
+        _ => check_socket_dir(dir)?,
+    }
+    // A socket file left behind by an agent that did not shut down cleanly.
+    if socket_path.exists() {
+        fs::remove_file(&socket_path)?;
+    }
+    // Create the socket without group or world access from the start.
+    // SAFETY: umask has no preconditions; the previous mask is restored right after.
+    let previous_umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(&socket_path);
+    // SAFETY: as above.
+    unsafe { libc::umask(previous_umask) };
+    let listener =
+        listener.map_err(|e| eyre!("Failed to bind agent socket {:?}: {}", socket_path, e))?;
+    info!("Agent listening on {:?}", socket_path);
+
+    let state = Arc::new(Mutex::new(AgentState {
+        keys: HashMap::new(),
+        ttl,
+        idle_timeout,
+    }));
+
+    let expiry_state = Arc::clone(&state);
+    thread::spawn(move || {
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            if let Ok(mut state) = expiry_state.lock() {
+                state.expire();
+            }
+        }
+    });
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                // Each client gets its own thread, so a silent one cannot
+                // hold up the others.
+                let state = Arc::clone(&state);
+                let socket_path = socket_path.clone();
+                thread::spawn(move || serve_client(stream, &state, &socket_path));
+            }
+            Err(e) => warn!("Failed to accept agent connection: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Answers the request of one client. The request is read in full before
+/// taking the lock, which is only held while handling it.
+fn serve_client(mut stream: UnixStream, state: &Mutex<AgentState>, socket_path: &Path) {
+    let frames = match check_peer(&stream).and_then(|_| read_request(&mut stream)) {
+        Ok(frames) => frames,
+        Err(e) => {
+            debug!("Agent request failed: {}", e);
+            return;
+        }
+    };
+    let Ok(mut guard) = state.lock() else {
+        warn!("Agent state poisoned.");
+        return;
+    };
+    guard.expire();
+    let (status, payload, keep_running) = handle_request(&frames, &mut guard);
+    drop(guard);
+
+    if let Err(e) =
+        write_frame(&mut stream, status).and_then(|_| write_frame(&mut stream, &payload))
+    {
+        debug!("Failed to answer agent request: {}", e);
+    }
+    if !keep_running {
+        if let Err(e) = fs::remove_file(socket_path) {
+            warn!("Failed to remove agent socket {:?}: {}", socket_path, e);
+        }
+        info!("Agent stopped.");
+        std::process::exit(0);
+    }
+}
+
+/// Reads the frames of a single request, giving up on clients that stall.
+fn read_request(stream: &mut UnixStream) -> Result<Vec<Vec<u8>>> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let count = read_frame(stream)?;
+    let count = u32::from_be_bytes(
+        count
+            .try_into()
+            .map_err(|_| eyre!("Malformed agent request."))?,
+    );
+    (0..count).map(|_| read_frame(stream)).collect()
+}
+
+/// Serves a single request, returning the status and payload to send back
+/// and whether the agent should keep running.
+fn handle_request(frames: &[Vec<u8>], state: &mut AgentState) -> (&'static [u8], Vec<u8>, bool) {
+    match frames {
+        [op, fingerprint, ciphertext] if op == OP_DECRYPT => {
+            let fingerprint = String::from_utf8_lossy(fingerprint);
+            match state.keys.get_mut(fingerprint.as_ref()) {
+                Some(key) => match key.keyring.decrypt(ciphertext) {
+                    Ok(plaintext) => {
+                        key.last_used = Instant::now();
+                        (STATUS_OK, plaintext, true)
+                    }
+                    Err(e) => (STATUS_ERROR, e.to_string().into_bytes(), true),
+                },
+                None => (STATUS_LOCKED, Vec::new(), true),
+            }
+        }
+        [op, tsk, password] if op == OP_CACHE => {
+            let cached = Cert::from_bytes(tsk)
+                .map_err(|e| eyre!(e))
+                .and_then(|cert| Keyring::unlocked(cert, Password::from(password.clone())));
+            match cached {
+                Ok(keyring) => {
+                    let fingerprint = keyring.cert().fingerprint().to_hex();
+                    info!("Caching key {}", fingerprint);
+                    let now = Instant::now();
+                    state.keys.insert(
+                        fingerprint,
+                        CachedKey {
+                            keyring,
+                            unlocked_at: now,
+                            last_used: now,
+                        },
+                    );
+                    (STATUS_OK, Vec::new(), true)
+                }
+                Err(e) => (STATUS_ERROR, e.to_string().into_bytes(), true),
+            }
+        }
+        [op] if op == OP_LOCK => {
+            info!("Locking: wiping {} key(s).", state.keys.len());
+            state.keys.clear();
+            (STATUS_OK, Vec::new(), true)
+        }
+        [op] if op == OP_STATUS => {
+            let mut lines: Vec<String> = state
+                .keys
+                .iter()
+                .map(|(fingerprint, key)| {
+                    let ttl_left = state.ttl.saturating_sub(key.unlocked_at.elapsed());
+                    let idle_left = state.idle_timeout.saturating_sub(key.last_used.elapsed());
+                    format!(
+                        "{} (expires in {}s)",
+                        fingerprint,
+                        ttl_left.min(idle_left).as_secs()
+                    )
+                })
+                .collect();
+            lines.sort();
+            (STATUS_OK, lines.join("\n").into_bytes(), true)
+        }
+        [op] if op == OP_STOP => {
+            state.keys.clear();
+            (STATUS_OK, Vec::new(), false)
+        }
+        _ => (STATUS_ERROR, b"unknown request".to_vec(), true),
+    }
+}
@@ -1,6 +1,14 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
-use crate::constants::DEFAULT_PASSWORD_LENGTH;
+use clap::{Args, Parser, Subcommand};
+use eyre::Result;
+
+use crate::{
+    config::{Config, get_config_file_path},
+    context::Context,
+    generator::PasswordPolicy,
+    passphrase::PassphraseSource,
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -14,6 +22,74 @@ pub struct Cli {
     /// or prompts to create one if the default does not exist.
     #[arg(long)]
     pub key_path: Option<String>,
+
+    /// Optional: Path to the password store directory.
+    /// Takes precedence over the ZPS_STORE_DIR and PASSWORD_STORE_DIR
+    /// environment variables and the config file; defaults to ~/.zps.
+    #[arg(long)]
+    pub store: Option<PathBuf>,
+
+    /// Optional: Path to the config file (defaults to ~/.config/zps/config.toml).
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Optional: Named profile from the config file to use for this invocation.
+    #[arg(long, env = "ZPS_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Print more diagnostics on stderr (-vv for trace output).
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only print errors on stderr.
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Optional: Read the key password from the first line of this file descriptor.
+    #[arg(long, value_name = "FD")]
+    pub passphrase_fd: Option<i32>,
+
+    /// Optional: Read the key password from the first line of this file.
+    #[arg(long, env = "ZPS_PASSPHRASE_FILE", value_name = "PATH")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Optional: Program that prints the key password, called with the prompt as argument.
+    #[arg(long, env = "ZPS_ASKPASS", value_name = "PROGRAM")]
+    pub askpass: Option<String>,
+}
+
+impl Cli {
+    /// Path of the config file, from `--config` or the default location.
+    pub fn config_path(&self) -> Result<PathBuf> {
+        match &self.config {
+            Some(path) => Ok(path.clone()),
+            None => get_config_file_path(),
+        }
+    }
+
+    /// Merges the flags of this invocation with the loaded config.
+    /// Flags always take precedence over config values.
+    pub fn merge_config(&self, config: Config) -> Result<Context> {
+        let config_path = self.config_path()?;
+
+        let passphrase = PassphraseSource::resolve(
+            self.passphrase_fd,
+            self.passphrase_file
+                .clone()
+                .or_else(|| config.passphrase_file.clone()),
+            self.askpass.clone().or_else(|| config.askpass.clone()),
+        );
+
+        let mut ctx = Context::resolve(
+            config,
+            config_path,
+            self.profile.as_deref(),
+            self.store.as_deref(),
+            self.key_path.clone(),
+        )?;
+        ctx.passphrase = passphrase;
+        Ok(ctx)
+    }
 }
 
 #[derive(Subcommand)]
@@ -38,12 +114,57 @@ pub enum Commands {
         #[clap(long, short = 'a', default_value_t = false)]
         /// Show entire file
         show_all: bool,
+        /// Show only line N (1-based).
+        #[clap(long, value_name = "N", conflicts_with_all = ["show_all", "field"])]
+        line: Option<usize>,
+        /// Show only the value of a `NAME: value` line after the password.
+        #[clap(long, value_name = "NAME", conflicts_with = "show_all")]
+        field: Option<String>,
+        /// Copy to the clipboard instead of printing, restoring it after a timeout.
+        #[clap(long, short = 'c')]
+        clip: bool,
     },
     /// List passwords
     List {
         /// Optional subfolder to list
         #[clap(value_name = "SUBFOLDER")]
         subfolder: Option<String>,
+
+        /// Also show the effective recipients of the listed folders.
+        #[clap(long)]
+        recipients: bool,
+    },
+    /// Find passwords by name
+    Find {
+        /// Terms to match against entry paths (an entry matches if any term does)
+        #[clap(value_name = "TERMS", required = true)]
+        terms: Vec<String>,
+
+        /// Treat terms as glob patterns (e.g., "work/*mail*").
+        #[clap(long, short = 'g', conflicts_with = "fuzzy")]
+        glob: bool,
+
+        /// Match terms fuzzily.
+        #[clap(long, short = 'z')]
+        fuzzy: bool,
+
+        /// Print one matching path per line instead of a tree.
+        #[clap(long)]
+        flat: bool,
+    },
+    /// Search the decrypted contents of passwords
+    Grep {
+        /// Regular expression to search for
+        #[clap(value_name = "PATTERN")]
+        pattern: String,
+
+        /// Optional subfolder to search
+        #[clap(value_name = "SUBFOLDER")]
+        subfolder: Option<String>,
+
+        /// Match case-insensitively.
+        #[clap(long, short = 'i')]
+        ignore_case: bool,
     },
     /// Remove a password or folder
     Remove {
@@ -55,12 +176,175 @@ pub enum Commands {
         #[clap(long, short)]
         recursive: bool,
     },
+    /// Move or rename a password or folder
+    Mv {
+        /// Path of the password entry or folder to move
+        #[clap(value_name = "SRC")]
+        src: String,
+
+        /// New path (an existing folder or a path ending in '/' moves SRC into it)
+        #[clap(value_name = "DST")]
+        dst: String,
+
+        /// Overwrite the destination if it exists.
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Copy a password or folder, re-encrypting it for the destination
+    Cp {
+        /// Path of the password entry or folder to copy
+        #[clap(value_name = "SRC")]
+        src: String,
+
+        /// Destination path (an existing folder or a path ending in '/' copies SRC into it)
+        #[clap(value_name = "DST")]
+        dst: String,
+
+        /// Copy recursively (required for folders).
+        #[clap(long, short)]
+        recursive: bool,
+
+        /// Overwrite existing entries at the destination.
+        #[clap(long, short)]
+        force: bool,
+
+        /// Copy into the store of this profile instead of the current one.
+        #[clap(long, value_name = "NAME", conflicts_with = "to_store")]
+        to_profile: Option<String>,
+
+        /// Copy into the store at this path instead of the current one.
+        #[clap(long, value_name = "PATH")]
+        to_store: Option<PathBuf>,
+    },
     /// Edit an existing password
     Edit {
         /// Path to the password entry or folder
         #[clap(value_name = "PATH")]
         path: String,
     },
+    /// Re-encrypt entries whose recipients have changed
+    Reencrypt {
+        /// Optional subfolder to re-encrypt (defaults to the whole store)
+        #[clap(value_name = "SUBFOLDER")]
+        subfolder: Option<String>,
+    },
+    /// Manage the recipients entries are encrypted to
+    Recipients {
+        #[clap(subcommand)]
+        command: RecipientsCommands,
+    },
+    /// Manage named store profiles
+    Profile {
+        #[clap(subcommand)]
+        command: ProfileCommands,
+    },
+    /// Manage the agent that keeps the unlocked key in memory
+    Agent {
+        #[clap(subcommand)]
+        command: AgentCommands,
+    },
+    /// Restore the clipboard after a timeout (started by `--clip`)
+    #[clap(hide = true)]
+    ClipboardRestore {
+        #[clap(long)]
+        timeout: u64,
+        #[clap(long)]
+        copy_command: String,
+        #[clap(long)]
+        paste_command: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RecipientsCommands {
+    /// Add a recipient from a cert file or the fingerprint of a cert already in the store
+    Add {
+        /// Armored cert file or fingerprint
+        #[clap(value_name = "CERT_FILE|FINGERPRINT")]
+        recipient: String,
+
+        /// Folder whose recipients to change (defaults to the store root).
+        #[clap(long, short = 'p', value_name = "SUBFOLDER")]
+        path: Option<String>,
+
+        /// Re-encrypt the affected entries afterwards.
+        #[clap(long)]
+        reencrypt: bool,
+    },
+    /// Remove a recipient
+    Remove {
+        /// Fingerprint or cert file of the recipient
+        #[clap(value_name = "FINGERPRINT|CERT_FILE")]
+        recipient: String,
+
+        /// Folder whose recipients to change (defaults to the store root).
+        #[clap(long, short = 'p', value_name = "SUBFOLDER")]
+        path: Option<String>,
+
+        /// Re-encrypt the affected entries afterwards.
+        #[clap(long)]
+        reencrypt: bool,
+    },
+    /// List the effective recipients of a folder
+    List {
+        /// Folder to inspect (defaults to the store root).
+        #[clap(long, short = 'p', value_name = "SUBFOLDER")]
+        path: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ProfileCommands {
+    /// List configured profiles
+    List,
+    /// Add a new profile to the config file
+    Add {
+        /// Name of the profile (e.g., "ops" or "personal")
+        #[clap(value_name = "NAME")]
+        name: String,
+
+        /// Path to the password store of this profile.
+        #[clap(long, value_name = "PATH")]
+        store: PathBuf,
+
+        /// Optional: Path to the PGP secret key used with this profile.
+        #[clap(long, value_name = "PATH")]
+        key_path: Option<PathBuf>,
+
+        /// Do not commit changes to this profile's store automatically.
+        #[clap(long)]
+        no_auto_commit: bool,
+    },
+    /// Remove a profile from the config file (the store itself is kept)
+    Remove {
+        /// Name of the profile
+        #[clap(value_name = "NAME")]
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AgentCommands {
+    /// Start the agent in the background
+    Start {
+        /// Seconds a key stays cached after it was unlocked (defaults to the config).
+        #[clap(long, value_name = "SECONDS")]
+        ttl: Option<u64>,
+
+        /// Seconds a cached key may go unused before it is wiped (defaults to the config).
+        #[clap(long, value_name = "SECONDS")]
+        idle_timeout: Option<u64>,
+
+        /// Run in the foreground instead of detaching.
+        #[clap(long)]
+        foreground: bool,
+    },
+    /// Wipe all keys held by the agent
+    Lock,
+    /// Show the keys held by the agent
+    Status,
+    /// Stop the agent
+    Stop,
 }
 
 #[derive(Subcommand)]
@@ -79,27 +363,191 @@ pub enum PassCommands {
         #[clap(long, short = 'g')] // Added short flag -g
         generate: bool,
 
-        /// Optional length for the generated password if --generate is used.
-        /// Defaults to the same default as the 'generate' command.
-        #[clap(long, requires = "generate")]
-        length: Option<usize>,
+        /// Policy for the generated password if --generate is used.
+        #[clap(flatten)]
+        policy: PolicyArgs,
+
+        /// Read the whole entry from stdin until EOF.
+        #[clap(long, short = 'm', conflicts_with_all = ["generate", "echo"])]
+        multiline: bool,
+
+        /// Show the password while typing it.
+        #[clap(long, short = 'e', conflicts_with = "generate")]
+        echo: bool,
+
+        /// Do not ask for the password a second time.
+        #[clap(long, conflicts_with_all = ["generate", "multiline"])]
+        no_confirm: bool,
 
-        /// Optional: do not use symbols if --generate is used with --length.
-        #[clap(long, requires = "generate")] // Technically, only requires `generate`
-        no_symbols: bool,
+        /// Metadata line appended after the password, e.g. --field username=alice.
+        #[clap(long = "field", value_name = "KEY=VALUE")]
+        fields: Vec<String>,
+
+        /// Store the password even if it is below the configured minimum strength.
+        #[clap(long, conflicts_with = "generate")]
+        allow_weak: bool,
     },
     /// Generate a new password
     Generate {
-        /// Length of the generated password.
-        #[clap(long, short = 'l', default_value_t = DEFAULT_PASSWORD_LENGTH)]
-        length: usize,
+        #[clap(flatten)]
+        policy: PolicyArgs,
+    },
+    /// Replace the password of an entry with a generated one, keeping its other lines.
+    /// Follows the nearest recipe (`<entry>.recipe` or a folder's `.recipe`).
+    Rotate {
+        /// Path to the password entry
+        #[clap(value_name = "PATH")]
+        path: String,
+
+        /// Copy the new password to the clipboard instead of printing it.
+        #[clap(long, short = 'c')]
+        clip: bool,
 
-        /// Exclude symbols from the generated password.
-        #[clap(long, short = 'n', alias = "no-symbols")]
-        no_symbols: bool,
+        #[clap(flatten)]
+        policy: PolicyArgs,
     },
 }
 
+/// Flags overriding the configured `[generator]` policy.
+#[derive(Args, Debug)]
+#[clap(next_help_heading = "Generator options")]
+pub struct PolicyArgs {
+    /// Length of the generated password.
+    /// Defaults to the configured generator length (12 if unset).
+    #[clap(long, short = 'l')]
+    pub length: Option<usize>,
+
+    /// Exclude lowercase letters.
+    #[clap(long)]
+    pub no_lowercase: bool,
+
+    /// Exclude uppercase letters.
+    #[clap(long)]
+    pub no_uppercase: bool,
+
+    /// Exclude digits.
+    #[clap(long)]
+    pub no_digits: bool,
+
+    /// Exclude symbols from the generated password.
+    #[clap(long, short = 'n')]
+    pub no_symbols: bool,
+
+    /// Require at least N lowercase letters.
+    #[clap(long, value_name = "N")]
+    pub min_lowercase: Option<usize>,
+
+    /// Require at least N uppercase letters.
+    #[clap(long, value_name = "N")]
+    pub min_uppercase: Option<usize>,
+
+    /// Require at least N digits.
+    #[clap(long, value_name = "N")]
+    pub min_digits: Option<usize>,
+
+    /// Require at least N symbols.
+    #[clap(long, value_name = "N")]
+    pub min_symbols: Option<usize>,
+
+    /// Extra characters to draw from, e.g. --allow ' ' for spaces.
+    #[clap(long, value_name = "CHARS")]
+    pub allow: Option<String>,
+
+    /// Characters never to use, e.g. --forbid '<>&'.
+    #[clap(long, value_name = "CHARS")]
+    pub forbid: Option<String>,
+
+    /// Leave out look-alike characters such as 0/O and 1/l.
+    #[clap(long)]
+    pub no_ambiguous: bool,
+
+    /// Generate a passphrase of N words from the bundled wordlist instead.
+    #[clap(long, short = 'w', value_name = "N")]
+    pub words: Option<usize>,
+
+    /// Separator between passphrase words (default "-").
+    #[clap(long, value_name = "SEP")]
+    pub separator: Option<String>,
+
+    /// Capitalize each passphrase word.
+    #[clap(long)]
+    pub capitalize: bool,
+
+    /// Append a random digit to the passphrase.
+    #[clap(long)]
+    pub append_digit: bool,
+
+    /// Append a random symbol to the passphrase.
+    #[clap(long)]
+    pub append_symbol: bool,
+}
+
+impl PolicyArgs {
+    /// Whether any option was given on the command line.
+    pub fn is_set(&self) -> bool {
+        self.length.is_some()
+            || self.no_lowercase
+            || self.no_uppercase
+            || self.no_digits
+            || self.no_symbols
+            || self.min_lowercase.is_some()
+            || self.min_uppercase.is_some()
+            || self.min_digits.is_some()
+            || self.min_symbols.is_some()
+            || self.allow.is_some()
+            || self.forbid.is_some()
+            || self.no_ambiguous
+            || self.words.is_some()
+            || self.separator.is_some()
+            || self.capitalize
+            || self.append_digit
+            || self.append_symbol
+    }
+
+    /// Returns `base` with the given options applied on top.
+    pub fn apply(&self, base: &PasswordPolicy) -> PasswordPolicy {
+        let mut policy = base.clone();
+        if let Some(length) = self.length {
+            policy.length = length;
+            // An explicit length asks for characters, even where a recipe sets words.
+            policy.words = None;
+        }
+        policy.lowercase &= !self.no_lowercase;
+        policy.uppercase &= !self.no_uppercase;
+        policy.digits &= !self.no_digits;
+        policy.symbols &= !self.no_symbols;
+        if let Some(min) = self.min_lowercase {
+            policy.min_lowercase = min;
+        }
+        if let Some(min) = self.min_uppercase {
+            policy.min_uppercase = min;
+        }
+        if let Some(min) = self.min_digits {
+            policy.min_digits = min;
+        }
+        if let Some(min) = self.min_symbols {
+            policy.min_symbols = min;
+        }
+        if let Some(allow) = &self.allow {
+            policy.allowed.push_str(allow);
+        }
+        if let Some(forbid) = &self.forbid {
+            policy.forbidden.push_str(forbid);
+        }
+        policy.exclude_ambiguous |= self.no_ambiguous;
+        if let Some(words) = self.words {
+            policy.words = Some(words);
+        }
+        if let Some(separator) = &self.separator {
+            policy.separator = separator.clone();
+        }
+        policy.capitalize |= self.capitalize;
+        policy.append_digit |= self.append_digit;
+        policy.append_symbol |= self.append_symbol;
+        policy
+    }
+}
+
 #[derive(Subcommand)]
 pub enum OtpCommands {
     /// Generate current OTP code
@@ -107,6 +555,9 @@ pub enum OtpCommands {
         /// Path to OTP entry
         #[clap(value_name = "PATH")]
         path: String,
+        /// Copy the code to the clipboard instead of printing it.
+        #[clap(long, short = 'c')]
+        clip: bool,
     },
     /// Add new OTP entry
     Add {
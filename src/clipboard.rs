@@ -0,0 +1,200 @@
+use std::{
+    env,
+    io::{Read, Write},
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use eyre::{Result, eyre};
+use tracing::{debug, info};
+
+use crate::context::Context;
+
+/// Clipboard tools zps knows how to drive, as (name, copy command, paste command).
+const KNOWN_CLIPBOARD_TOOLS: &[(&str, &str, &str)] = &[
+    ("wl-copy", "wl-copy", "wl-paste --no-newline"),
+    (
+        "xclip",
+        "xclip -selection clipboard",
+        "xclip -selection clipboard -o",
+    ),
+    (
+        "xsel",
+        "xsel --clipboard --input",
+        "xsel --clipboard --output",
+    ),
+    ("pbcopy", "pbcopy", "pbpaste"),
+];
+
+/// Shell commands that write the clipboard from stdin and print it to stdout.
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    pub copy_command: String,
+    /// Without a paste command the clipboard is cleared instead of restored.
+    pub paste_command: Option<String>,
+}
+
+fn command_exists(name: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(name).is_file()))
+}
+
+impl Clipboard {
+    /// Builds the clipboard from the configured command, which is either the
+    /// name of a known tool or a custom shell command. Without one, the first
+    /// available tool for the running display server is used.
+    pub fn resolve(copy_command: Option<&str>, paste_command: Option<&str>) -> Result<Self> {
+        let preset = |name: &str| {
+            KNOWN_CLIPBOARD_TOOLS
+                .iter()
+                .find(|(tool, _, _)| *tool == name)
+                .map(|(_, copy, paste)| Clipboard {
+                    copy_command: copy.to_string(),
+                    paste_command: Some(paste.to_string()),
+                })
+        };
+
+        if let Some(command) = copy_command {
+            return Ok(match preset(command) {
+                Some(clipboard) if paste_command.is_none() => clipboard,
+                _ => Clipboard {
+                    copy_command: command.to_string(),
+                    paste_command: paste_command.map(str::to_string),
+                },
+            });
+        }
+
+        let candidates: &[&str] = if env::var_os("WAYLAND_DISPLAY").is_some() {
+            &["wl-copy", "xclip", "xsel"]
+        } else if env::var_os("DISPLAY").is_some() {
+            &["xclip", "xsel"]
+        } else {
+            &["pbcopy"]
+        };
+        candidates
+            .iter()
+            .find(|tool| command_exists(tool))
+            .and_then(|tool| preset(tool))
+            .ok_or_else(|| {
+                eyre!("No clipboard tool found. Install wl-copy, xclip or xsel, or set clipboard_command in the config.")
+            })
+    }
+
+    /// Replaces the clipboard contents with `text`.
+    pub fn copy(&self, text: &str) -> Result<()> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.copy_command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| eyre!("Failed to run '{}': {}", self.copy_command, e))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(eyre!("'{}' exited with {}.", self.copy_command, status));
+        }
+        Ok(())
+    }
+
+    /// Returns the clipboard contents, or `None` without a paste command.
+    pub fn paste(&self) -> Result<Option<String>> {
+        let Some(paste_command) = &self.paste_command else {
+            return Ok(None);
+        };
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(paste_command)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|e| eyre!("Failed to run '{}': {}", paste_command, e))?;
+        // An empty clipboard makes some tools exit with an error.
+        if !output.status.success() {
+            return Ok(Some(String::new()));
+        }
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+}
+
+/// Copies the secret of entry `name` to the clipboard configured in `ctx`
+/// instead of printing it.
+pub fn clip_secret(ctx: &Context, name: &str, secret: &str) -> Result<()> {
+    copy_to_clipboard(&ctx.clipboard()?, secret, ctx.clipboard_timeout)?;
+    if ctx.clipboard_timeout == 0 {
+        info!("Copied {} to clipboard.", name);
+    } else {
+        info!(
+            "Copied {} to clipboard. Will restore it in {} seconds.",
+            name, ctx.clipboard_timeout
+        );
+    }
+    Ok(())
+}
+
+/// Copies `secret` to the clipboard and, unless `timeout` is zero, starts a
+/// detached `zps` process that restores the previous contents afterwards.
+pub fn copy_to_clipboard(clipboard: &Clipboard, secret: &str, timeout: u64) -> Result<()> {
+    let previous = clipboard.paste()?.unwrap_or_default();
+    clipboard.copy(secret)?;
+
+    if timeout == 0 {
+        return Ok(());
+    }
+
+    let mut command = Command::new(env::current_exe()?);
+    command
+        .args(["clipboard-restore", "--timeout", &timeout.to_string()])
+        .arg("--copy-command")
+        .arg(&clipboard.copy_command);
+    if let Some(paste_command) = &clipboard.paste_command {
+        command.arg("--paste-command").arg(paste_command);
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Outlive the terminal's process group so closing it does not skip the restore.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| eyre!("Failed to start clipboard restore: {}", e))?;
+
+    // Secrets travel over stdin rather than argv, which other users can read.
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(stdin, "{}\n{}{}", secret.len(), secret, previous)?;
+    }
+    Ok(())
+}
+
+/// Runs in the detached process: reads the secret and the previous contents
+/// from stdin, waits `timeout` seconds, then puts the previous contents back
+/// unless the clipboard has been changed in the meantime.
+pub fn restore_clipboard(clipboard: &Clipboard, timeout: u64) -> Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    let (len, rest) = input
+        .split_once('\n')
+        .ok_or_else(|| eyre!("Malformed clipboard restore input."))?;
+    let len: usize = len.parse()?;
+    if !rest.is_char_boundary(len) {
+        return Err(eyre!("Malformed clipboard restore input."));
+    }
+    let (secret, previous) = rest.split_at(len);
+
+    thread::sleep(Duration::from_secs(timeout));
+
+    if let Some(current) = clipboard.paste()?
+        && current != secret
+    {
+        debug!("Clipboard changed since copying, leaving it alone.");
+        return Ok(());
+    }
+    clipboard.copy(previous)?;
+    info!("Clipboard restored.");
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use std::{
+    os::unix::process::CommandExt,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use eyre::{Result, eyre};
+use tracing::info;
+
+use crate::{
+    agent::{
+        agent_is_running, agent_lock, agent_status, agent_stop, get_agent_socket_path, run_agent,
+    },
+    context::Context,
+};
+
+/// Start the agent, detached from the terminal unless `foreground` is set.
+/// TTL and idle timeout default to the `[agent]` section of the config.
+pub fn handle_agent_start(
+    ctx: &Context,
+    ttl: Option<u64>,
+    idle_timeout: Option<u64>,
+    foreground: bool,
+) -> Result<()> {
+    let ttl = ttl.unwrap_or(ctx.config.agent.ttl);
+    let idle_timeout = idle_timeout.unwrap_or(ctx.config.agent.idle_timeout);
+
+    if foreground {
+        return run_agent(Duration::from_secs(ttl), Duration::from_secs(idle_timeout));
+    }
+
+    if agent_is_running() {
+        info!("Agent is already running on {:?}.", get_agent_socket_path());
+        return Ok(());
+    }
+
+    let mut command = Command::new(std::env::current_exe()?);
+    if ctx.config_path.exists() {
+        command.arg("--config").arg(&ctx.config_path);
+    }
+    command
+        .args(["agent", "start", "--foreground"])
+        .arg("--ttl")
+        .arg(ttl.to_string())
+        .arg("--idle-timeout")
+        .arg(idle_timeout.to_string())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        // Detach from the terminal's process group so Ctrl-C does not stop it.
+        .process_group(0)
+        .spawn()
+        .map_err(|e| eyre!("Failed to start agent: {}", e))?;
+
+    for _ in 0..50 {
+        if agent_is_running() {
+            info!("Agent started on {:?}.", get_agent_socket_path());
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    Err(eyre!(
+        "Agent did not come up on {:?}.",
+        get_agent_socket_path()
+    ))
+}
+
+/// Wipe every key held by the agent.
+pub fn handle_agent_lock() -> Result<()> {
+    if agent_lock()? {
+        info!("Agent locked.");
+    } else {
+        info!("No agent is running.");
+    }
+    Ok(())
+}
+
+/// Show the keys held by the agent and when they expire.
+pub fn handle_agent_status() -> Result<()> {
+    match agent_status()? {
+        Some(status) if status.is_empty() => {
+            info!(
+                "Agent is running on {:?} and holds no keys.",
+                get_agent_socket_path()
+            );
+        }
+        Some(status) => {
+            info!("Agent is running on {:?}.", get_agent_socket_path());
+            println!("{}", status);
+        }
+        None => info!("No agent is running."),
+    }
+    Ok(())
+}
+
+/// Stop the agent, wiping its keys.
+pub fn handle_agent_stop() -> Result<()> {
+    if agent_stop()? {
+        info!("Agent stopped.");
+    } else {
+        info!("No agent is running.");
+    }
+    Ok(())
+}
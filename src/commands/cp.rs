@@ -0,0 +1,183 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use sequoia_openpgp::Cert;
+use tracing::info;
+use walkdir::WalkDir;
+
+use crate::{
+    context::Context,
+    gpg::{Keyring, encrypt_data},
+    recipe::{is_recipe_file, sidecar_recipe_targets},
+    recipients::resolve_recipients,
+    store::{ensure_store_directory_exists, get_password_file_path, resolve_destination_name},
+    utils::determine_key,
+};
+
+/// Copies a single entry by decrypting it with `src_keyring` and encrypting it
+/// to the recipients of its destination.
+fn copy_entry(
+    src_file_path: &Path,
+    dst_store_path: &Path,
+    dst_file_path: &Path,
+    src_keyring: &Keyring,
+    dst_recipients: &[Cert],
+) -> Result<()> {
+    let encrypted_data = fs::read(src_file_path)?;
+    let decrypted_bytes = src_keyring
+        .decrypt(&encrypted_data)
+        .map_err(|e| eyre!("Failed to decrypt {:?}: {}", src_file_path, e))?;
+
+    if let Some(parent_dir) = dst_file_path.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+    fs::write(
+        dst_file_path,
+        encrypt_data(&decrypted_bytes, dst_recipients)?,
+    )?;
+    info!(
+        "Copied to '{}'",
+        dst_file_path
+            .strip_prefix(dst_store_path)
+            .unwrap_or(dst_file_path)
+            .with_extension("")
+            .display()
+    );
+    Ok(())
+}
+
+/// Copy a password entry or folder, optionally into another profile's store.
+/// Entries are decrypted and re-encrypted so the destination's recipients apply.
+pub fn handle_cp(
+    ctx: &Context,
+    src: &str,
+    dst: &str,
+    recursive: bool,
+    force: bool,
+    to_profile: Option<&str>,
+    to_store: Option<&Path>,
+) -> Result<()> {
+    let home_dir_str = std::env::var("HOME")?;
+    let home_dir = PathBuf::from(home_dir_str);
+    let (src_cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+    let src_keyring = ctx.keyring(src_cert);
+
+    ensure_store_directory_exists(&ctx.store_path)?;
+
+    let cross_store = to_profile.is_some() || to_store.is_some();
+    let dst_ctx_owned = if cross_store {
+        Some(ctx.for_target(to_profile, to_store)?)
+    } else {
+        None
+    };
+    let dst_ctx = dst_ctx_owned.as_ref().unwrap_or(ctx);
+    let dst_store_path = dst_ctx.store_path.as_path();
+    ensure_store_directory_exists(dst_store_path)?;
+    let dst_cert = if cross_store {
+        determine_key(&home_dir, dst_ctx.key_path.clone())?.0
+    } else {
+        src_keyring.cert().clone()
+    };
+
+    let src_file_path = get_password_file_path(&ctx.store_path, src)?;
+    let src_dir_path = ctx.store_path.join(src);
+    let dst_name = resolve_destination_name(dst_store_path, src, dst)?;
+
+    // Pairs of (source file, destination file) to copy.
+    let mut copies: Vec<(PathBuf, PathBuf)> = Vec::new();
+    if src_dir_path.is_dir() {
+        if !recursive {
+            return Err(eyre!(
+                "Error: '{}' is a directory. Use --recursive (-r) to copy.",
+                src
+            ));
+        }
+        let dst_dir_path = dst_store_path.join(&dst_name);
+        if !cross_store && dst_dir_path.starts_with(&src_dir_path) {
+            return Err(eyre!("Cannot copy '{}' into itself.", src));
+        }
+        for entry in WalkDir::new(&src_dir_path)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            let path = entry.path();
+            // Recipes in the folder are carried along with its entries.
+            if path.is_file()
+                && (path.extension().is_some_and(|ext| ext == "gpg") || is_recipe_file(path))
+                && let Ok(rel_path) = path.strip_prefix(&src_dir_path)
+            {
+                copies.push((path.to_path_buf(), dst_dir_path.join(rel_path)));
+            }
+        }
+    } else if src_file_path.is_file() {
+        let dst_file_path = get_password_file_path(dst_store_path, &dst_name)?;
+        if dst_file_path == src_file_path {
+            return Err(eyre!("Source and destination are the same: '{}'", src));
+        }
+        let sidecar_recipes = sidecar_recipe_targets(&src_file_path, &dst_file_path);
+        copies.push((src_file_path, dst_file_path));
+        copies.extend(sidecar_recipes);
+    } else {
+        return Err(eyre!(
+            "Error: '{}' not found as a password or directory.",
+            src
+        ));
+    }
+
+    if copies.iter().all(|(src_path, _)| is_recipe_file(src_path)) {
+        info!("'{}' contains no entries. Nothing to copy.", src);
+        return Ok(());
+    }
+
+    if !force && let Some((_, existing)) = copies.iter().find(|(_, dst_path)| dst_path.exists()) {
+        return Err(eyre!(
+            "Password entry '{}' already exists. Use --force to overwrite.",
+            existing
+                .strip_prefix(dst_store_path)
+                .unwrap_or(existing)
+                .with_extension("")
+                .display()
+        ));
+    }
+
+    let mut recipients_by_dir: HashMap<PathBuf, Vec<Cert>> = HashMap::new();
+    for (src_path, dst_path) in &copies {
+        if src_path.extension().is_none_or(|ext| ext != "gpg") {
+            // Plain recipes are copied as they are.
+            if let Some(parent_dir) = dst_path.parent() {
+                fs::create_dir_all(parent_dir)?;
+            }
+            fs::copy(src_path, dst_path)?;
+            continue;
+        }
+        let dir = dst_path.parent().unwrap_or(dst_store_path).to_path_buf();
+        let recipients = match recipients_by_dir.get(&dir) {
+            Some(recipients) => recipients,
+            None => {
+                let resolved = resolve_recipients(dst_store_path, dst_path, &dst_cert)?;
+                recipients_by_dir.entry(dir).or_insert(resolved)
+            }
+        };
+        copy_entry(src_path, dst_store_path, dst_path, &src_keyring, recipients)?;
+    }
+
+    let commit_message = if cross_store {
+        format!(
+            "Copy {} from {} to {}",
+            src,
+            ctx.store_path.display(),
+            dst_name
+        )
+    } else {
+        format!("Copy {} to {}", src, dst_name)
+    };
+    dst_ctx.commit(&commit_message)?;
+
+    Ok(())
+}
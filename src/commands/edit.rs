@@ -3,27 +3,28 @@ use std::{fs, path::PathBuf};
 use tracing::info;
 
 use crate::{
+    context::Context,
+    entry::Entry,
     gpg::{decrypt_data, encrypt_data},
-    store::{ensure_store_directory_exists, get_password_file_path, get_password_store_path},
+    recipients::resolve_recipients,
+    store::{ensure_store_directory_exists, get_password_file_path},
     utils::{determine_key, edit_content_in_editor},
-    vcs::jj_commit_changes,
 };
 
-pub fn handle_edit(path: &str, key_path: Option<String>) -> Result<()> {
+pub fn handle_edit(ctx: &Context, path: &str) -> Result<()> {
     let home_dir_str = std::env::var("HOME")?;
     let home_dir = PathBuf::from(home_dir_str);
-    let (cert, _) = determine_key(&home_dir, key_path)?;
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
 
-    let store_path = get_password_store_path()?;
-    ensure_store_directory_exists(&store_path)?;
+    ensure_store_directory_exists(&ctx.store_path)?;
 
-    let password_file_path = get_password_file_path(&store_path, path)?;
+    let password_file_path = get_password_file_path(&ctx.store_path, path)?;
     let file_existed_before_edit = password_file_path.exists();
 
     let initial_content = if file_existed_before_edit {
         info!("Editing existing entry: {}", path);
         let encrypted_data = fs::read(&password_file_path)?;
-        let decrypted_bytes = decrypt_data(&cert, &encrypted_data)?;
+        let decrypted_bytes = decrypt_data(&cert, &encrypted_data, &ctx.passphrase)?;
         String::from_utf8(decrypted_bytes).map_err(|e| {
             eyre!(
                 "Failed to decode decrypted content for '{}' as UTF-8: {}",
@@ -36,14 +37,17 @@ pub fn handle_edit(path: &str, key_path: Option<String>) -> Result<()> {
         "\n".to_string()
     };
 
-    let modified_content = edit_content_in_editor(&initial_content)?;
+    let initial_entry = Entry::parse(&initial_content);
+    let modified_content =
+        edit_content_in_editor(&initial_entry.to_string(), ctx.editor.as_deref())?;
+    let modified_entry = Entry::parse(&modified_content);
 
-    if modified_content.trim().is_empty() {
+    if modified_entry.is_empty() {
         if file_existed_before_edit {
             info!("Content is empty after editing. Removing entry '{}'.", path);
             fs::remove_file(&password_file_path)?;
             let commit_message = format!("Remove entry {} (edited to empty)", path);
-            jj_commit_changes(&store_path, &commit_message)?;
+            ctx.commit(&commit_message)?;
             info!("Entry '{}' removed as it was saved empty.", path);
         } else {
             info!("New entry '{}' was saved empty. No file created.", path);
@@ -51,11 +55,18 @@ pub fn handle_edit(path: &str, key_path: Option<String>) -> Result<()> {
         return Ok(());
     }
 
+    if file_existed_before_edit && modified_entry == initial_entry {
+        info!("Entry '{}' unchanged.", path);
+        return Ok(());
+    }
+
     if let Some(parent_dir) = password_file_path.parent() {
         fs::create_dir_all(parent_dir)?;
     }
 
-    let encrypted_data = encrypt_data(modified_content.as_bytes(), &cert)?;
+    let recipients = resolve_recipients(&ctx.store_path, &password_file_path, &cert)?;
+
+    let encrypted_data = encrypt_data(modified_entry.to_string().as_bytes(), &recipients)?;
     fs::write(&password_file_path, encrypted_data)?;
 
     let (action_message, commit_action_prefix) = if !file_existed_before_edit {
@@ -66,7 +77,7 @@ pub fn handle_edit(path: &str, key_path: Option<String>) -> Result<()> {
     info!("{}", action_message);
 
     let commit_message = format!("{} entry {}", commit_action_prefix, path);
-    jj_commit_changes(&store_path, &commit_message)?;
+    ctx.commit(&commit_message)?;
 
     Ok(())
 }
@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use eyre::{Result, eyre};
+use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
+use glob::{MatchOptions, Pattern};
+use tracing::info;
+
+use crate::{
+    commands::list::{build_display_tree, collect_entry_paths},
+    context::Context,
+    store::ensure_store_directory_exists,
+};
+
+/// How search terms are matched against entry paths.
+enum Matcher {
+    /// Case-insensitive substring match.
+    Substring(Vec<String>),
+    /// Shell-style glob match on the whole entry path.
+    Glob(Vec<Pattern>),
+    /// Fuzzy subsequence match, scored by the skim algorithm.
+    Fuzzy(Box<SkimMatcherV2>, Vec<String>),
+}
+
+impl Matcher {
+    fn new(terms: &[String], glob: bool, fuzzy: bool) -> Result<Self> {
+        if glob {
+            let patterns = terms
+                .iter()
+                .map(|t| Pattern::new(t).map_err(|e| eyre!("Invalid glob '{}': {}", t, e)))
+                .collect::<Result<_>>()?;
+            Ok(Matcher::Glob(patterns))
+        } else if fuzzy {
+            Ok(Matcher::Fuzzy(
+                Box::new(SkimMatcherV2::default().ignore_case()),
+                terms.to_vec(),
+            ))
+        } else {
+            Ok(Matcher::Substring(
+                terms.iter().map(|t| t.to_lowercase()).collect(),
+            ))
+        }
+    }
+
+    /// Returns the best score of `entry` against any term, or `None` if no term matches.
+    /// Substring and glob matches all score zero.
+    fn score(&self, entry: &str) -> Option<i64> {
+        match self {
+            Matcher::Substring(terms) => {
+                let entry = entry.to_lowercase();
+                terms.iter().any(|t| entry.contains(t)).then_some(0)
+            }
+            Matcher::Glob(patterns) => {
+                let options = MatchOptions {
+                    case_sensitive: false,
+                    require_literal_separator: false,
+                    require_literal_leading_dot: false,
+                };
+                patterns
+                    .iter()
+                    .any(|p| p.matches_with(entry, options))
+                    .then_some(0)
+            }
+            Matcher::Fuzzy(matcher, terms) => terms
+                .iter()
+                .filter_map(|t| matcher.fuzzy_match(entry, t))
+                .max(),
+        }
+    }
+}
+
+/// Find entries whose path matches any of `terms`, printing a pruned tree,
+/// or one path per line with `flat` (best fuzzy matches first).
+pub fn handle_find(
+    ctx: &Context,
+    terms: &[String],
+    glob: bool,
+    fuzzy: bool,
+    flat: bool,
+) -> Result<()> {
+    let store_path = ctx.store_path.as_path();
+    ensure_store_directory_exists(store_path)?;
+
+    let matcher = Matcher::new(terms, glob, fuzzy)?;
+
+    let mut matches: Vec<(i64, PathBuf)> = collect_entry_paths(store_path)
+        .into_iter()
+        .filter_map(|rel_path| {
+            let entry = rel_path.to_string_lossy().replace('\\', "/");
+            matcher.score(&entry).map(|score| (score, rel_path))
+        })
+        .collect();
+
+    if matches.is_empty() {
+        info!("No entries match '{}'.", terms.join(" "));
+        return Ok(());
+    }
+
+    if flat {
+        // Stable sort keeps name order among equally scored entries.
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        for (_, rel_path) in matches {
+            println!("{}", rel_path.display());
+        }
+    } else {
+        let relative_entry_paths: Vec<PathBuf> = matches.into_iter().map(|(_, p)| p).collect();
+        let tree = build_display_tree(
+            format!("Search Terms: {}", terms.join(" ")),
+            &relative_entry_paths,
+        );
+        println!("{}", tree);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::{fs, path::PathBuf};
+
+use eyre::{Result, eyre};
+use regex::RegexBuilder;
+use tracing::warn;
+use walkdir::WalkDir;
+
+use crate::{
+    context::Context, entry::Entry, gpg::WrongPassword, recipe::is_recipe_file,
+    store::ensure_store_directory_exists, utils::determine_key,
+};
+
+/// Search the decrypted contents of every entry for `pattern`,
+/// printing each matching entry followed by its matching lines.
+/// The key is unlocked once and reused for all entries.
+pub fn handle_grep(
+    ctx: &Context,
+    pattern: &str,
+    subfolder: Option<&str>,
+    ignore_case: bool,
+) -> Result<()> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| eyre!("Invalid pattern '{}': {}", pattern, e))?;
+
+    let home_dir_str = std::env::var("HOME")?;
+    let home_dir = PathBuf::from(home_dir_str);
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+    let keyring = ctx.keyring(cert);
+
+    let store_path = ctx.store_path.as_path();
+    ensure_store_directory_exists(store_path)?;
+
+    let search_base_path = match subfolder {
+        Some(sf) if !sf.is_empty() => store_path.join(sf),
+        _ => store_path.to_path_buf(),
+    };
+    if !search_base_path.is_dir() {
+        let display_path = subfolder.unwrap_or("Password Store root");
+        return Err(eyre!(
+            "Error: '{}' is not a directory or does not exist.",
+            display_path
+        ));
+    }
+
+    for entry in WalkDir::new(&search_base_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if !path.is_file()
+            || path.extension().is_none_or(|ext| ext != "gpg")
+            || is_recipe_file(path)
+        {
+            continue;
+        }
+        let entry_name = path
+            .strip_prefix(store_path)
+            .unwrap_or(path)
+            .with_extension("");
+
+        let encrypted_data = fs::read(path)?;
+        let decrypted_content = match keyring
+            .decrypt(&encrypted_data)
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|e| eyre!(e)))
+        {
+            Ok(content) => content,
+            Err(e) if e.downcast_ref::<WrongPassword>().is_some() => return Err(e),
+            Err(e) => {
+                warn!("Skipping '{}': {}", entry_name.display(), e);
+                continue;
+            }
+        };
+
+        let entry = Entry::parse(&decrypted_content);
+        let matching_lines: Vec<&str> = entry.lines().filter(|line| regex.is_match(line)).collect();
+        if !matching_lines.is_empty() {
+            println!("{}:", entry_name.display());
+            for line in matching_lines {
+                println!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
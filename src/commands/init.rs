@@ -4,22 +4,19 @@ use eyre::Result;
 use tracing::info;
 
 use crate::{
-    store::{ensure_store_directory_exists, get_password_store_path},
-    utils::determine_key,
+    context::Context, store::ensure_store_directory_exists, utils::determine_key,
     vcs::jj_init_repository,
 };
 
-pub fn handle_init_command(key_path: Option<String>) -> Result<()> {
+pub fn handle_init_command(ctx: &Context) -> Result<()> {
     let home_dir_str = std::env::var("HOME")?;
     let home_dir = PathBuf::from(home_dir_str);
 
-    let store_path = get_password_store_path()?;
+    ensure_store_directory_exists(&ctx.store_path)?;
 
-    ensure_store_directory_exists(&store_path)?;
+    jj_init_repository(&ctx.store_path)?;
 
-    jj_init_repository(&store_path)?;
-
-    let (_, used_key_file_path) = determine_key(&home_dir, key_path)?;
+    let (_, used_key_file_path) = determine_key(&home_dir, ctx.key_path.clone())?;
 
     info!("Password store initialized successfully");
     info!("The PGP key file being used is: {:?}", used_key_file_path);
@@ -1,15 +1,28 @@
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
 use eyre::{Result, eyre};
 use termtree::Tree;
 use tracing::info;
 use walkdir::WalkDir;
 
-use crate::store::{ensure_store_directory_exists, get_password_store_path};
+use crate::{
+    context::Context,
+    recipe::is_recipe_file,
+    recipients::{
+        RECIPIENTS_FILE_NAME, describe_recipient, find_recipients_file, read_recipients_file,
+    },
+    store::ensure_store_directory_exists,
+};
 
 // Helper to recursively build the tree structure for termtree
 // `entries` should be a collection of paths relative to the listing_base_path, with .gpg removed.
-fn build_display_tree(base_display_name: String, relative_entry_paths: &[PathBuf]) -> Tree<String> {
+pub fn build_display_tree(
+    base_display_name: String,
+    relative_entry_paths: &[PathBuf],
+) -> Tree<String> {
     let mut root = Tree::new(base_display_name);
     let mut children_map = BTreeMap::<String, Vec<PathBuf>>::new();
 
@@ -39,14 +52,97 @@ fn build_display_tree(base_display_name: String, relative_entry_paths: &[PathBuf
     root
 }
 
-pub fn handle_list(subfolder: Option<&str>) -> Result<()> {
-    let store_path = get_password_store_path()?;
+/// Collects the entries below `base_path` as paths relative to it, with .gpg removed,
+/// sorted by name. Encrypted recipes are left out.
+pub fn collect_entry_paths(base_path: &Path) -> Vec<PathBuf> {
+    let mut relative_entry_paths: Vec<PathBuf> = Vec::new();
+    for entry in WalkDir::new(base_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if path.is_file()
+            && path.extension().is_some_and(|ext| ext == "gpg")
+            && !is_recipe_file(path)
+            && let Ok(rel_path) = path.strip_prefix(base_path)
+        {
+            relative_entry_paths.push(rel_path.with_extension(""));
+        }
+    }
+    relative_entry_paths
+}
+
+// Formats the recipients file governing `folder` as "<folder> (<file>): <recipients>".
+fn describe_folder_recipients(
+    store_path: &Path,
+    folder: &Path,
+    display_name: &str,
+) -> Result<String> {
+    let Some(recipients_file) = find_recipients_file(store_path, folder) else {
+        return Ok(format!(
+            "{}: your own key (no {} file)",
+            display_name, RECIPIENTS_FILE_NAME
+        ));
+    };
+
+    let recipients: Vec<String> = read_recipients_file(&recipients_file)?
+        .iter()
+        .map(|spec| describe_recipient(store_path, spec))
+        .collect();
+    let source = recipients_file
+        .strip_prefix(store_path)
+        .unwrap_or(&recipients_file);
+    Ok(format!(
+        "{} ({}): {}",
+        display_name,
+        source.display(),
+        recipients.join(", ")
+    ))
+}
+
+// Lists the effective recipients of `listing_base_path` and of every folder below it
+// that overrides them with its own recipients file.
+fn list_recipients(
+    store_path: &Path,
+    listing_base_path: &Path,
+    base_display_name: &str,
+) -> Result<()> {
+    let mut lines = vec![describe_folder_recipients(
+        store_path,
+        listing_base_path,
+        base_display_name,
+    )?];
+
+    for entry in WalkDir::new(listing_base_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if path.is_dir()
+            && path.join(RECIPIENTS_FILE_NAME).is_file()
+            && let Ok(rel_path) = path.strip_prefix(store_path)
+        {
+            let display_name = rel_path.to_string_lossy();
+            lines.push(describe_folder_recipients(store_path, path, &display_name)?);
+        }
+    }
 
-    ensure_store_directory_exists(&store_path)?;
+    println!("Recipients:\n{}", lines.join("\n"));
+    Ok(())
+}
+
+pub fn handle_list(ctx: &Context, subfolder: Option<&str>, show_recipients: bool) -> Result<()> {
+    let store_path = ctx.store_path.as_path();
+
+    ensure_store_directory_exists(store_path)?;
 
     let listing_base_path = match subfolder {
         Some(sf) if !sf.is_empty() => store_path.join(sf),
-        _ => store_path.clone(),
+        _ => store_path.to_path_buf(),
     };
 
     if !listing_base_path.is_dir() {
@@ -57,27 +153,14 @@ pub fn handle_list(subfolder: Option<&str>) -> Result<()> {
         ));
     }
 
-    let mut relative_entry_paths: Vec<PathBuf> = Vec::new();
-    for entry in WalkDir::new(&listing_base_path)
-        .min_depth(1)
-        .sort_by_file_name()
-        .into_iter()
-        .filter_map(Result::ok)
-    {
-        let path = entry.path();
-        if path.is_file() && path.extension().is_some_and(|ext| ext == "gpg") {
-            if let Ok(rel_path) = path.strip_prefix(&listing_base_path) {
-                relative_entry_paths.push(rel_path.with_extension(""));
-            }
-        }
-    }
+    let relative_entry_paths = collect_entry_paths(&listing_base_path);
 
     if relative_entry_paths.is_empty() {
         let display_base = if listing_base_path == store_path {
             "Password Store".to_string()
         } else {
             listing_base_path
-                .strip_prefix(&store_path)
+                .strip_prefix(store_path)
                 .unwrap_or(&listing_base_path)
                 .to_string_lossy()
                 .into_owned()
@@ -96,8 +179,12 @@ pub fn handle_list(subfolder: Option<&str>) -> Result<()> {
             .into_owned()
     };
 
-    let tree = build_display_tree(tree_root_name, &relative_entry_paths);
-    info!("{}", tree);
+    let tree = build_display_tree(tree_root_name.clone(), &relative_entry_paths);
+    println!("{}", tree);
+
+    if show_recipients {
+        list_recipients(store_path, &listing_base_path, &tree_root_name)?;
+    }
 
     Ok(())
 }
@@ -1,7 +1,15 @@
+pub mod agent;
+pub mod cp;
 pub mod edit;
+pub mod find;
+pub mod grep;
 pub mod init;
 pub mod list;
+pub mod mv;
 pub mod otp;
 pub mod pass;
+pub mod profile;
+pub mod recipients;
+pub mod reencrypt;
 pub mod remove;
 pub mod show;
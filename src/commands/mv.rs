@@ -0,0 +1,162 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use tempfile::{Builder, TempDir};
+use tracing::info;
+use walkdir::WalkDir;
+
+use crate::{
+    commands::reencrypt::{reencrypt_entries, reencrypt_entry},
+    context::Context,
+    recipe::{sidecar_recipe_paths, sidecar_recipe_targets},
+    recipients::resolve_recipients,
+    store::{ensure_store_directory_exists, get_password_file_path, resolve_destination_name},
+    utils::determine_key,
+};
+
+/// Prefix of the hidden staging folders a move prepares its result in.
+const STAGING_PREFIX: &str = ".zps-mv-";
+
+/// Copies the folder `src` with everything in it to `dst`.
+fn copy_dir(src: &Path, dst: &Path) -> Result<()> {
+    for entry in WalkDir::new(src).into_iter() {
+        let entry = entry?;
+        let target = dst.join(entry.path().strip_prefix(src)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&target)?;
+        } else {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Creates a hidden staging folder next to `path`, so that renaming out of it
+/// stays on the same filesystem and entries in it resolve the same recipients.
+fn staging_dir_for(path: &Path) -> Result<TempDir> {
+    let parent_dir = path
+        .parent()
+        .ok_or_else(|| eyre!("{:?} has no parent folder.", path))?;
+    fs::create_dir_all(parent_dir)?;
+    Ok(Builder::new()
+        .prefix(STAGING_PREFIX)
+        .tempdir_in(parent_dir)?)
+}
+
+/// Puts `staged` in place of `dst`. An existing `dst` is only moved aside,
+/// and is restored if the swap fails.
+fn swap_into_place(staged: &Path, dst: &Path) -> Result<()> {
+    if !dst.exists() {
+        fs::rename(staged, dst)?;
+        return Ok(());
+    }
+    let backup_dir = staging_dir_for(dst)?;
+    let backup = backup_dir.path().join("previous");
+    fs::rename(dst, &backup)?;
+    if let Err(e) = fs::rename(staged, dst) {
+        fs::rename(&backup, dst)?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Move or rename a password entry or folder.
+/// If DST is an existing folder (or ends with '/'), SRC is moved into it.
+/// Entries are re-encrypted when their new location has different recipients.
+pub fn handle_mv(ctx: &Context, src: &str, dst: &str, force: bool) -> Result<()> {
+    let store_path = ctx.store_path.as_path();
+    ensure_store_directory_exists(store_path)?;
+
+    let home_dir_str = std::env::var("HOME")?;
+    let home_dir = PathBuf::from(home_dir_str);
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+    let keyring = ctx.keyring(cert);
+
+    let src_file_path = get_password_file_path(store_path, src)?;
+    let src_dir_path = store_path.join(src);
+
+    let dst_name = resolve_destination_name(store_path, src, dst)?;
+
+    // The result is staged next to the destination and only swapped in once
+    // every entry is re-encrypted, so a failure leaves SRC and DST untouched.
+    if src_dir_path.is_dir() {
+        let dst_dir_path = store_path.join(&dst_name);
+        if dst_dir_path.starts_with(&src_dir_path) {
+            return Err(eyre!("Cannot move '{}' into itself.", src));
+        }
+        if src_dir_path.starts_with(&dst_dir_path) {
+            return Err(eyre!("Cannot move '{}' over a folder containing it.", src));
+        }
+        if dst_dir_path.exists() && !force {
+            return Err(eyre!(
+                "'{}' already exists. Use --force to overwrite.",
+                dst_name
+            ));
+        }
+
+        let staging_dir = staging_dir_for(&dst_dir_path)?;
+        let staged_dir_path = staging_dir.path().join("entries");
+        copy_dir(&src_dir_path, &staged_dir_path)?;
+        reencrypt_entries(store_path, &staged_dir_path, &keyring)?;
+
+        swap_into_place(&staged_dir_path, &dst_dir_path)?;
+        fs::remove_dir_all(&src_dir_path)?;
+        info!("Folder '{}' moved to '{}'.", src, dst_name);
+    } else if src_file_path.is_file() {
+        let dst_file_path = get_password_file_path(store_path, &dst_name)?;
+        if dst_file_path == src_file_path {
+            return Err(eyre!("Source and destination are the same: '{}'", src));
+        }
+        if dst_file_path.exists() && !force {
+            return Err(eyre!(
+                "Password entry '{}' already exists. Use --force to overwrite.",
+                dst_name
+            ));
+        }
+
+        // The entry's own recipe moves along with it.
+        let mut moves = vec![(src_file_path.clone(), dst_file_path.clone())];
+        moves.extend(sidecar_recipe_targets(&src_file_path, &dst_file_path));
+
+        let staging_dir = staging_dir_for(&dst_file_path)?;
+        let recipients = resolve_recipients(store_path, &dst_file_path, keyring.cert())?;
+        let mut staged = Vec::new();
+        for (src_path, dst_path) in &moves {
+            let staged_path = staging_dir
+                .path()
+                .join(dst_path.file_name().unwrap_or_default());
+            fs::copy(src_path, &staged_path)?;
+            if staged_path.extension().is_some_and(|ext| ext == "gpg") {
+                reencrypt_entry(store_path, &staged_path, &recipients, &keyring)?;
+            }
+            staged.push((staged_path, dst_path));
+        }
+
+        for (staged_path, dst_path) in staged {
+            fs::rename(staged_path, dst_path)?;
+        }
+        // A recipe of the overwritten entry would otherwise apply to the moved one.
+        for stale_recipe in sidecar_recipe_paths(&dst_file_path) {
+            if stale_recipe.is_file() && !moves.iter().any(|(_, dst)| *dst == stale_recipe) {
+                fs::remove_file(&stale_recipe)?;
+            }
+        }
+        for (src_path, _) in &moves {
+            fs::remove_file(src_path)?;
+        }
+        info!("Password '{}' moved to '{}'.", src, dst_name);
+    } else {
+        return Err(eyre!(
+            "Error: '{}' not found as a password or directory.",
+            src
+        ));
+    }
+
+    let commit_message = format!("Rename {} to {}", src, dst_name);
+    ctx.commit(&commit_message)?;
+
+    Ok(())
+}
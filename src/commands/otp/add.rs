@@ -5,10 +5,11 @@ use totp_rs::TOTP;
 use tracing::info;
 
 use crate::{
+    context::Context,
     gpg::encrypt_data,
-    store::{ensure_store_directory_exists, get_password_file_path, get_password_store_path},
+    recipients::resolve_recipients,
+    store::{ensure_store_directory_exists, get_password_file_path},
     utils::determine_key,
-    vcs::jj_commit_changes,
 };
 
 /// Validate and normalize otpauth URI
@@ -25,21 +26,21 @@ fn normalize_otpauth_uri(uri: &str) -> Result<String> {
 }
 
 /// Create new OTP entry
-pub fn handle_otp_add(path: &str, uri: &str, key_path: Option<String>) -> Result<()> {
+pub fn handle_otp_add(ctx: &Context, path: &str, uri: &str) -> Result<()> {
     let home_dir_str = std::env::var("HOME")?;
     let home_dir = PathBuf::from(home_dir_str);
-    let (cert, _) = determine_key(&home_dir, key_path)?;
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
 
-    let store_path = get_password_store_path()?;
+    ensure_store_directory_exists(&ctx.store_path)?;
 
-    ensure_store_directory_exists(&store_path)?;
-
-    let otp_file_path = get_password_file_path(&store_path, path)?;
+    let otp_file_path = get_password_file_path(&ctx.store_path, path)?;
 
     let uri = normalize_otpauth_uri(uri)?;
     let content = uri.clone();
 
-    let encrypted = encrypt_data(content.as_bytes(), &cert)?;
+    let recipients = resolve_recipients(&ctx.store_path, &otp_file_path, &cert)?;
+
+    let encrypted = encrypt_data(content.as_bytes(), &recipients)?;
     if let Some(parent) = otp_file_path.parent() {
         std::fs::create_dir_all(parent)?;
     }
@@ -48,7 +49,7 @@ pub fn handle_otp_add(path: &str, uri: &str, key_path: Option<String>) -> Result
     info!("OTP entry created at {}", path);
 
     let commit_message = format!("Add OTP for {}", path);
-    jj_commit_changes(&store_path, &commit_message)?;
+    ctx.commit(&commit_message)?;
 
     Ok(())
 }
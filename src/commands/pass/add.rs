@@ -1,67 +1,144 @@
 use eyre::{Result, eyre};
 use rpassword::prompt_password;
+use std::cell::Cell;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
 use std::path::PathBuf;
 use tracing::info;
 
-use crate::commands::pass::generate::generate_password_internal;
-use crate::constants::DEFAULT_PASSWORD_LENGTH;
+use crate::cli::PolicyArgs;
+use crate::context::Context;
+use crate::entry::{Entry, Field};
+use crate::generator::generate_password;
 use crate::gpg::encrypt_data;
-use crate::store::{
-    ensure_store_directory_exists, get_password_file_path, get_password_store_path,
-};
+use crate::output::prompt;
+use crate::recipe::resolve_policy;
+use crate::recipients::resolve_recipients;
+use crate::store::{ensure_store_directory_exists, get_password_file_path};
 use crate::utils::determine_key;
-use crate::vcs::jj_commit_changes;
+
+/// How the content of a new entry is obtained.
+pub enum PasswordInput {
+    /// Prompt for the password without echoing it, asking twice with `confirm`.
+    /// A password piped into stdin is read without prompting.
+    Prompt { confirm: bool },
+    /// Read the password from a visible line on stdin.
+    Echo,
+    /// Read the whole entry from stdin until EOF.
+    Multiline,
+    /// Generate the password, with the options given on top of the entry's recipe.
+    Generate(PolicyArgs),
+}
+
+/// Parses a `--field KEY=VALUE` argument.
+fn parse_field_arg(arg: &str) -> Result<Field> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| eyre!("Invalid field '{}', expected KEY=VALUE.", arg))?;
+    let key = key.trim();
+    if key.is_empty() || key.contains([':', '\n', '\r']) {
+        return Err(eyre!(
+            "Invalid field name '{}': it must be non-empty without ':' or line breaks.",
+            key
+        ));
+    }
+    if value.contains(['\n', '\r']) {
+        return Err(eyre!(
+            "Invalid value for field '{}': it cannot contain line breaks.",
+            key
+        ));
+    }
+    Ok(Field::new(key, value.trim()))
+}
+
+/// Reads one line from stdin without its line ending.
+fn read_stdin_line() -> Result<String> {
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
 
 fn get_password_content(
     path_name: &str,
-    generate_flag: bool,
-    length_opt: Option<usize>,
-    no_symbols_opt: bool,
+    input: &PasswordInput,
+    generate: &dyn Fn() -> Result<String>,
 ) -> Result<String> {
-    let mut lines: Vec<String> = Vec::new();
-
-    if generate_flag {
-        let length = length_opt.unwrap_or(DEFAULT_PASSWORD_LENGTH);
-        let no_symbols = no_symbols_opt;
-        let password = generate_password_internal(length, no_symbols);
-        lines.push(password);
-    } else {
-        let password = prompt_password(format!("Enter password for {}: ", path_name))?;
-        if password.is_empty() {
-            info!("Empty password, generating one.");
-            let gen_length = DEFAULT_PASSWORD_LENGTH;
-            let gen_no_symbols = false;
-            lines.push(generate_password_internal(gen_length, gen_no_symbols));
-        } else {
-            lines.push(password);
+    let password = match input {
+        PasswordInput::Generate(_) => generate()?,
+        PasswordInput::Multiline => {
+            prompt(&format!(
+                "Enter contents of {} and press Ctrl+D when finished:\n",
+                path_name
+            ))?;
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content)?;
+            content
         }
-    }
+        PasswordInput::Prompt { .. } | PasswordInput::Echo => {
+            let message = format!("Enter password for {}: ", path_name);
+            let password = match input {
+                PasswordInput::Prompt { confirm } if io::stdin().is_terminal() => {
+                    let password = prompt_password(&message)?;
+                    if *confirm && !password.is_empty() {
+                        let repeated =
+                            prompt_password(format!("Retype password for {}: ", path_name))?;
+                        if repeated != password {
+                            return Err(eyre!("The entered passwords do not match."));
+                        }
+                    }
+                    password
+                }
+                PasswordInput::Prompt { .. } => read_stdin_line()?,
+                _ => {
+                    prompt(&message)?;
+                    read_stdin_line()?
+                }
+            };
+            if password.is_empty() && !io::stdin().is_terminal() {
+                // Nobody would ever see a password generated for empty piped input.
+                return Err(eyre!(
+                    "No password received on stdin for {}. Use --generate to create one.",
+                    path_name
+                ));
+            }
+            if password.is_empty() {
+                info!("Empty password, generating one.");
+                generate()?
+            } else {
+                password
+            }
+        }
+    };
 
-    if lines.is_empty() || lines[0].is_empty() {
+    if password.trim().is_empty() {
         return Err(eyre!("Password content cannot be empty."));
     }
 
-    Ok(lines.join("\n"))
+    Ok(password)
 }
 
+/// Add an entry, followed by the metadata `fields` given as `KEY=VALUE`.
+/// Entered passwords below the configured strength are refused unless `allow_weak`.
 pub fn handle_pass_add(
+    ctx: &Context,
     path: &str,
     force: bool,
-    generate: bool,
-    length: Option<usize>,
-    no_symbols: bool,
-    key_path: Option<String>,
+    input: PasswordInput,
+    fields: &[String],
+    allow_weak: bool,
 ) -> Result<()> {
+    let fields = fields
+        .iter()
+        .map(|arg| parse_field_arg(arg))
+        .collect::<Result<Vec<_>>>()?;
+
     let home_dir_str = std::env::var("HOME")?;
     let home_dir = PathBuf::from(home_dir_str);
-    let (cert, _) = determine_key(&home_dir, key_path)?;
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
 
-    let store_path = get_password_store_path()?;
+    ensure_store_directory_exists(&ctx.store_path)?;
 
-    ensure_store_directory_exists(&store_path)?;
-
-    let password_file_path = get_password_file_path(&store_path, path)?;
+    let password_file_path = get_password_file_path(&ctx.store_path, path)?;
 
     if password_file_path.exists() && !force {
         return Err(eyre!(
@@ -74,16 +151,45 @@ pub fn handle_pass_add(
         fs::create_dir_all(parent_dir)?;
     }
 
-    let content = get_password_content(path, generate, length, no_symbols)?;
+    let generated = Cell::new(false);
+    let generate = || {
+        let keyring = ctx.keyring(cert.clone());
+        let base = resolve_policy(
+            &ctx.store_path,
+            &password_file_path,
+            &ctx.generator,
+            &keyring,
+        )?;
+        let policy = match &input {
+            PasswordInput::Generate(args) => args.apply(&base),
+            _ => base,
+        };
+        let password = generate_password(&policy)?;
+        ctx.strength.report_generated(&policy);
+        generated.set(true);
+        Ok(password)
+    };
+
+    let mut entry = Entry::parse(&get_password_content(path, &input, &generate)?);
+    if !generated.get() {
+        let subject = format!("The password for '{}'", path);
+        ctx.strength.check(&subject, entry.password(), allow_weak)?;
+    }
+    for field in fields {
+        entry.push_field(field);
+    }
+    let content = entry.to_string();
+
+    let recipients = resolve_recipients(&ctx.store_path, &password_file_path, &cert)?;
 
-    let encrypted_data = encrypt_data(content.as_bytes(), &cert)?;
+    let encrypted_data = encrypt_data(content.as_bytes(), &recipients)?;
 
     fs::write(&password_file_path, encrypted_data)?;
 
-    println!("Password for '{}' added.", path);
+    info!("Password for '{}' added.", path);
 
     let commit_message = format!("Add password for {}", path);
-    jj_commit_changes(&store_path, &commit_message)?;
+    ctx.commit(&commit_message)?;
 
     Ok(())
 }
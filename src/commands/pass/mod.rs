@@ -1,2 +1,3 @@
 pub mod add;
 pub mod generate;
+pub mod rotate;
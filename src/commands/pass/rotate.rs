@@ -0,0 +1,59 @@
+use eyre::{Result, eyre};
+use std::{fs, path::PathBuf};
+use tracing::info;
+
+use crate::{
+    cli::PolicyArgs, clipboard::clip_secret, context::Context, entry::Entry,
+    generator::generate_password, gpg::encrypt_data, output::print_secret, recipe::resolve_policy,
+    recipients::resolve_recipients, store::get_password_file_path, utils::determine_key,
+};
+
+/// Replaces the password of an existing entry with a newly generated one,
+/// keeping the rest of the entry. The entry's recipe applies, with the
+/// options given on top.
+pub fn handle_pass_rotate(
+    ctx: &Context,
+    path: &str,
+    overrides: &PolicyArgs,
+    clip: bool,
+) -> Result<()> {
+    let home_dir_str = std::env::var("HOME")?;
+    let home_dir = PathBuf::from(home_dir_str);
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+
+    let password_file_path = get_password_file_path(&ctx.store_path, path)?;
+    if !password_file_path.exists() {
+        return Err(eyre!("Password entry '{}' not found.", path));
+    }
+
+    let keyring = ctx.keyring(cert);
+    let decrypted_bytes = keyring.decrypt(&fs::read(&password_file_path)?)?;
+    let mut entry = Entry::parse(&String::from_utf8(decrypted_bytes)?);
+
+    let base = resolve_policy(
+        &ctx.store_path,
+        &password_file_path,
+        &ctx.generator,
+        &keyring,
+    )?;
+    let policy = overrides.apply(&base);
+    let password = generate_password(&policy)?;
+    ctx.strength.report_generated(&policy);
+    entry.set_password(&password);
+
+    let recipients = resolve_recipients(&ctx.store_path, &password_file_path, keyring.cert())?;
+    let encrypted_data = encrypt_data(entry.to_string().as_bytes(), &recipients)?;
+    fs::write(&password_file_path, encrypted_data)?;
+    info!("Password for '{}' rotated.", path);
+
+    if clip {
+        clip_secret(ctx, path, &password)?;
+    } else {
+        print_secret(&password)?;
+    }
+
+    let commit_message = format!("Rotate password for {}", path);
+    ctx.commit(&commit_message)?;
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::path::{Path, PathBuf};
+
+use eyre::{Result, eyre};
+use tracing::info;
+
+use crate::config::{Config, ProfileConfig, VcsConfig, add_profile, remove_profile};
+
+/// List the profiles from the config file, marking the active one.
+pub fn handle_profile_list(
+    config: &Config,
+    config_path: &Path,
+    active_profile: Option<&str>,
+) -> Result<()> {
+    if config.profiles.is_empty() {
+        info!("No profiles configured in {:?}.", config_path);
+        return Ok(());
+    }
+
+    for (name, profile) in &config.profiles {
+        let marker = if active_profile == Some(name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        let key = profile
+            .key_path
+            .as_ref()
+            .map(|p| format!(" (key: {})", p.display()))
+            .unwrap_or_default();
+        println!(
+            "{} {}\t{}{}",
+            marker,
+            name,
+            profile.store_path.display(),
+            key
+        );
+    }
+
+    Ok(())
+}
+
+/// Add a profile to the config file.
+pub fn handle_profile_add(
+    config_path: &Path,
+    name: &str,
+    store: PathBuf,
+    key_path: Option<PathBuf>,
+    no_auto_commit: bool,
+) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_whitespace) {
+        return Err(eyre!(
+            "Invalid profile name (cannot be empty or contain whitespace): '{}'",
+            name
+        ));
+    }
+
+    let profile = ProfileConfig {
+        store_path: std::path::absolute(store)?,
+        key_path: key_path.map(std::path::absolute).transpose()?,
+        vcs: no_auto_commit.then_some(VcsConfig { auto_commit: false }),
+    };
+    add_profile(config_path, name, &profile)?;
+
+    info!(
+        "Profile '{}' added for store {:?}.",
+        name, profile.store_path
+    );
+    Ok(())
+}
+
+/// Remove a profile from the config file. The store on disk is left untouched.
+pub fn handle_profile_remove(config_path: &Path, name: &str) -> Result<()> {
+    remove_profile(config_path, name)?;
+    info!("Profile '{}' removed.", name);
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use sequoia_openpgp::{Cert, serialize::SerializeInto};
+use tracing::info;
+
+use crate::{
+    commands::reencrypt::reencrypt_entries,
+    context::Context,
+    gpg::{check_recipient_cert, load_and_validate_key_from_file},
+    recipients::{
+        PUBLIC_KEYS_DIR_NAME, RECIPIENTS_FILE_NAME, RecipientSpec, append_recipient,
+        describe_recipient, find_recipients_file, public_key_path, read_recipients_file,
+        remove_recipients,
+    },
+    store::ensure_store_directory_exists,
+    utils::determine_key,
+};
+
+/// Resolves the folder a recipients command operates on, checking that it exists.
+fn resolve_folder(store_path: &Path, folder: Option<&str>) -> Result<PathBuf> {
+    let folder_path = match folder {
+        Some(f) if !f.is_empty() => {
+            if f.contains("..") || f.starts_with('/') || f.starts_with('\\') {
+                return Err(eyre!(
+                    "Invalid folder name (cannot contain '..' or be an absolute path): '{}'",
+                    f
+                ));
+            }
+            store_path.join(f)
+        }
+        _ => store_path.to_path_buf(),
+    };
+    if !folder_path.is_dir() {
+        return Err(eyre!(
+            "Error: '{}' is not a directory or does not exist.",
+            folder.unwrap_or("Password Store root")
+        ));
+    }
+    Ok(folder_path)
+}
+
+fn folder_display_name(folder: Option<&str>) -> &str {
+    match folder {
+        Some(f) if !f.is_empty() => f,
+        _ => "store root",
+    }
+}
+
+/// Stores the public part of `cert` in the store's public keys directory,
+/// so other users can encrypt to it. Returns its fingerprint.
+fn export_public_key(store_path: &Path, cert: &Cert) -> Result<String> {
+    let fingerprint = cert.fingerprint().to_hex();
+    let public_key_file = public_key_path(store_path, &fingerprint);
+    fs::create_dir_all(store_path.join(PUBLIC_KEYS_DIR_NAME))?;
+    let armored_public_key = cert
+        .armored()
+        .to_vec()
+        .map_err(|e| eyre!("Failed to serialize public key {}: {}", fingerprint, e))?;
+    fs::write(&public_key_file, armored_public_key)?;
+    info!("Stored public key {} at {:?}", fingerprint, public_key_file);
+    Ok(fingerprint)
+}
+
+/// Resolves a recipient given either as a cert file or as a fingerprint of a cert
+/// already in the store. Cert files are validated and their public part is copied
+/// into the store. Returns the validated fingerprint.
+fn import_recipient(store_path: &Path, recipient: &str) -> Result<String> {
+    let recipient_path = Path::new(recipient);
+    if recipient_path.is_file() {
+        let cert = load_and_validate_key_from_file(recipient_path)?;
+        check_recipient_cert(&cert)?;
+        return export_public_key(store_path, &cert);
+    }
+
+    match RecipientSpec::parse(recipient) {
+        RecipientSpec::Fingerprint(fingerprint) => {
+            let public_key_file = public_key_path(store_path, &fingerprint);
+            if !public_key_file.exists() {
+                return Err(eyre!(
+                    "No public key for {} in the store. Pass the cert file instead.",
+                    fingerprint
+                ));
+            }
+            check_recipient_cert(&load_and_validate_key_from_file(&public_key_file)?)?;
+            Ok(fingerprint)
+        }
+        RecipientSpec::CertFile(_) => Err(eyre!(
+            "'{}' is neither a cert file nor a fingerprint.",
+            recipient
+        )),
+    }
+}
+
+/// Re-encrypts `folder_path` after a recipient change and returns the commit message suffix.
+fn reencrypt_after_change(ctx: &Context, folder_path: &Path) -> Result<String> {
+    let home_dir = PathBuf::from(std::env::var("HOME")?);
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+    let keyring = ctx.keyring(cert);
+    let (reencrypted, _) = reencrypt_entries(&ctx.store_path, folder_path, &keyring)?;
+    info!("Re-encrypted {} entries.", reencrypted);
+    Ok(format!(" and reencrypt {} entries", reencrypted))
+}
+
+/// Add a recipient to the recipients file of `folder` (the store root by default).
+/// A folder without its own recipients file starts from the recipients it inherited.
+pub fn handle_recipients_add(
+    ctx: &Context,
+    recipient: &str,
+    folder: Option<&str>,
+    reencrypt: bool,
+) -> Result<()> {
+    ensure_store_directory_exists(&ctx.store_path)?;
+    let folder_path = resolve_folder(&ctx.store_path, folder)?;
+    let fingerprint = import_recipient(&ctx.store_path, recipient)?;
+
+    let recipients_file = folder_path.join(RECIPIENTS_FILE_NAME);
+    let inherited_file = find_recipients_file(&ctx.store_path, &folder_path);
+    let specs = match &inherited_file {
+        Some(file) => read_recipients_file(file)?,
+        None => {
+            // Without any recipients file entries are encrypted to the user's own key;
+            // keep it so adding a colleague does not lock the user out, and store its
+            // public part so the colleague can encrypt to it as well.
+            let home_dir = PathBuf::from(std::env::var("HOME")?);
+            let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+            vec![RecipientSpec::Fingerprint(export_public_key(
+                &ctx.store_path,
+                &cert,
+            )?)]
+        }
+    };
+
+    if specs
+        .iter()
+        .any(|spec| spec.fingerprint(&ctx.store_path).as_deref() == Some(&fingerprint))
+    {
+        info!(
+            "{} is already a recipient of {}.",
+            fingerprint,
+            folder_display_name(folder)
+        );
+        return Ok(());
+    }
+    match &inherited_file {
+        // Start from a copy of the inherited file so its comments carry over.
+        Some(file) if *file != recipients_file => {
+            fs::copy(file, &recipients_file)?;
+        }
+        Some(_) => {}
+        None => {
+            for spec in &specs {
+                append_recipient(&recipients_file, spec)?;
+            }
+        }
+    }
+    append_recipient(
+        &recipients_file,
+        &RecipientSpec::Fingerprint(fingerprint.clone()),
+    )?;
+    info!(
+        "Added {} to the recipients of {}.",
+        fingerprint,
+        folder_display_name(folder)
+    );
+
+    let mut commit_message = format!(
+        "Add recipient {} to {}",
+        fingerprint,
+        folder_display_name(folder)
+    );
+    if reencrypt {
+        commit_message.push_str(&reencrypt_after_change(ctx, &folder_path)?);
+    }
+    ctx.commit(&commit_message)?;
+
+    Ok(())
+}
+
+/// Remove a recipient from the recipients file of `folder` (the store root by default).
+pub fn handle_recipients_remove(
+    ctx: &Context,
+    recipient: &str,
+    folder: Option<&str>,
+    reencrypt: bool,
+) -> Result<()> {
+    ensure_store_directory_exists(&ctx.store_path)?;
+    let folder_path = resolve_folder(&ctx.store_path, folder)?;
+
+    let recipients_file = folder_path.join(RECIPIENTS_FILE_NAME);
+    if !recipients_file.is_file() {
+        return Err(eyre!(
+            "{} has no {} file of its own; remove the recipient where it is defined.",
+            folder_display_name(folder),
+            RECIPIENTS_FILE_NAME
+        ));
+    }
+
+    let fingerprint = match RecipientSpec::parse(recipient) {
+        RecipientSpec::Fingerprint(fpr) => fpr,
+        RecipientSpec::CertFile(path) => load_and_validate_key_from_file(&path)?
+            .fingerprint()
+            .to_hex(),
+    };
+
+    let is_removed =
+        |spec: &RecipientSpec| spec.fingerprint(&ctx.store_path).as_deref() == Some(&fingerprint);
+    let specs = read_recipients_file(&recipients_file)?;
+    let remaining = specs.iter().filter(|spec| !is_removed(spec)).count();
+    if remaining == specs.len() {
+        return Err(eyre!(
+            "{} is not a recipient of {}.",
+            fingerprint,
+            folder_display_name(folder)
+        ));
+    }
+    if remaining == 0 {
+        return Err(eyre!(
+            "Refusing to remove the last recipient of {}.",
+            folder_display_name(folder)
+        ));
+    }
+    remove_recipients(&recipients_file, is_removed)?;
+    info!(
+        "Removed {} from the recipients of {}.",
+        fingerprint,
+        folder_display_name(folder)
+    );
+
+    let mut commit_message = format!(
+        "Remove recipient {} from {}",
+        fingerprint,
+        folder_display_name(folder)
+    );
+    if reencrypt {
+        commit_message.push_str(&reencrypt_after_change(ctx, &folder_path)?);
+    }
+    ctx.commit(&commit_message)?;
+
+    Ok(())
+}
+
+/// List the effective recipients of `folder` (the store root by default).
+pub fn handle_recipients_list(ctx: &Context, folder: Option<&str>) -> Result<()> {
+    ensure_store_directory_exists(&ctx.store_path)?;
+    let folder_path = resolve_folder(&ctx.store_path, folder)?;
+
+    let Some(recipients_file) = find_recipients_file(&ctx.store_path, &folder_path) else {
+        info!(
+            "No {} file applies to {}; entries are encrypted to your own key.",
+            RECIPIENTS_FILE_NAME,
+            folder_display_name(folder)
+        );
+        return Ok(());
+    };
+
+    info!(
+        "Recipients of {} (from {:?}):",
+        folder_display_name(folder),
+        recipients_file
+            .strip_prefix(&ctx.store_path)
+            .unwrap_or(&recipients_file)
+    );
+    for spec in read_recipients_file(&recipients_file)? {
+        println!("{}", describe_recipient(&ctx.store_path, &spec));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use sequoia_openpgp::Cert;
+use tracing::info;
+use walkdir::WalkDir;
+
+use crate::{
+    context::Context,
+    gpg::{Keyring, encrypt_data, encryption_key_ids, message_recipient_key_ids},
+    recipe::is_recipe_file,
+    recipients::resolve_recipients,
+    store::ensure_store_directory_exists,
+    utils::determine_key,
+};
+
+/// Re-encrypts every entry below `base_path` whose current recipients differ from
+/// the recipients its folder resolves to, along with encrypted recipes.
+/// Returns the number of re-encrypted entries and recipes, counted separately.
+pub fn reencrypt_entries(
+    store_path: &Path,
+    base_path: &Path,
+    keyring: &Keyring,
+) -> Result<(usize, usize)> {
+    let mut recipients_by_dir: HashMap<PathBuf, Vec<Cert>> = HashMap::new();
+    let mut reencrypted = 0;
+    let mut reencrypted_recipes = 0;
+
+    for entry in WalkDir::new(base_path)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(Result::ok)
+    {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "gpg") {
+            continue;
+        }
+
+        let dir = path.parent().unwrap_or(store_path).to_path_buf();
+        let recipients = match recipients_by_dir.get(&dir) {
+            Some(recipients) => recipients,
+            None => {
+                let resolved = resolve_recipients(store_path, path, keyring.cert())?;
+                recipients_by_dir.entry(dir).or_insert(resolved)
+            }
+        };
+
+        if !reencrypt_entry(store_path, path, recipients, keyring)? {
+            continue;
+        }
+        if is_recipe_file(path) {
+            reencrypted_recipes += 1;
+        } else {
+            reencrypted += 1;
+        }
+    }
+
+    Ok((reencrypted, reencrypted_recipes))
+}
+
+/// Re-encrypts the entry file at `path` to `recipients` unless it is already
+/// encrypted to exactly their keys. Returns whether the entry was rewritten.
+pub fn reencrypt_entry(
+    store_path: &Path,
+    path: &Path,
+    recipients: &[Cert],
+    keyring: &Keyring,
+) -> Result<bool> {
+    let encrypted_data = fs::read(path)?;
+    if message_recipient_key_ids(&encrypted_data)? == encryption_key_ids(recipients) {
+        return Ok(false);
+    }
+
+    let entry_name = path
+        .strip_prefix(store_path)
+        .unwrap_or(path)
+        .with_extension("");
+    let decrypted_bytes = keyring
+        .decrypt(&encrypted_data)
+        .map_err(|e| eyre!("Failed to decrypt '{}': {}", entry_name.display(), e))?;
+    fs::write(path, encrypt_data(&decrypted_bytes, recipients)?)?;
+    info!("Re-encrypted '{}'", entry_name.display());
+
+    Ok(true)
+}
+
+pub fn handle_reencrypt(ctx: &Context, subfolder: Option<&str>) -> Result<()> {
+    let home_dir_str = std::env::var("HOME")?;
+    let home_dir = PathBuf::from(home_dir_str);
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
+    let keyring = ctx.keyring(cert);
+
+    ensure_store_directory_exists(&ctx.store_path)?;
+
+    let base_path = match subfolder {
+        Some(sf) if !sf.is_empty() => ctx.store_path.join(sf),
+        _ => ctx.store_path.clone(),
+    };
+    if !base_path.is_dir() {
+        let display_path = subfolder.unwrap_or("Password Store root");
+        return Err(eyre!(
+            "Error: '{}' is not a directory or does not exist.",
+            display_path
+        ));
+    }
+
+    let (reencrypted, reencrypted_recipes) =
+        reencrypt_entries(&ctx.store_path, &base_path, &keyring)?;
+    if reencrypted == 0 && reencrypted_recipes == 0 {
+        info!("All entries already match their recipients. Nothing to do.");
+        return Ok(());
+    }
+
+    let scope = match subfolder {
+        Some(sf) if !sf.is_empty() => sf,
+        _ => "store",
+    };
+    info!("Re-encrypted {} entries in {}.", reencrypted, scope);
+    if reencrypted_recipes > 0 {
+        info!("Re-encrypted {} recipes in {}.", reencrypted_recipes, scope);
+    }
+
+    let commit_message = format!("Reencrypt {} entries in {}", reencrypted, scope);
+    ctx.commit(&commit_message)?;
+
+    Ok(())
+}
@@ -2,15 +2,15 @@ use eyre::{Result, eyre};
 use tracing::info;
 
 use crate::{
-    store::{ensure_store_directory_exists, get_password_file_path, get_password_store_path},
-    vcs::jj_commit_changes,
+    context::Context,
+    store::{ensure_store_directory_exists, get_password_file_path},
 };
 
-pub fn handle_remove(path: &str, recursive: bool) -> Result<()> {
-    let store_path = get_password_store_path()?;
-    ensure_store_directory_exists(&store_path)?;
+pub fn handle_remove(ctx: &Context, path: &str, recursive: bool) -> Result<()> {
+    let store_path = ctx.store_path.as_path();
+    ensure_store_directory_exists(store_path)?;
 
-    let potential_gpg_file_path = get_password_file_path(&store_path, path)?;
+    let potential_gpg_file_path = get_password_file_path(store_path, path)?;
     let potential_dir_path = store_path.join(path);
 
     let original_path_for_message = path.to_string();
@@ -37,11 +37,11 @@ pub fn handle_remove(path: &str, recursive: bool) -> Result<()> {
         info!("Directory '{}' removed.", path);
     } else {
         std::fs::remove_file(&path_to_remove_fs)?;
-        println!("Password '{}' removed.", path);
+        info!("Password '{}' removed.", path);
     }
 
     let commit_message = format!("Remove entry {}", original_path_for_message);
-    jj_commit_changes(&store_path, &commit_message)?;
+    ctx.commit(&commit_message)?;
 
     Ok(())
 }
@@ -1,23 +1,33 @@
 use eyre::{Result, eyre};
-use std::{fs, io::Write, path::PathBuf};
-use tracing::info;
+use std::{fs, path::PathBuf};
 
 use crate::{
+    clipboard::clip_secret,
+    context::Context,
+    entry::Entry,
     gpg::decrypt_data,
-    store::{ensure_store_directory_exists, get_password_file_path, get_password_store_path},
+    output::print_secret,
+    store::{ensure_store_directory_exists, get_password_file_path},
     utils::determine_key,
 };
 
-pub fn handle_show(path: &str, show_all: bool, key_path: Option<String>) -> Result<()> {
+/// Show an entry: its first line by default, everything with `show_all`,
+/// or a single line or field.
+pub fn handle_show(
+    ctx: &Context,
+    path: &str,
+    show_all: bool,
+    line: Option<usize>,
+    field: Option<&str>,
+    clip: bool,
+) -> Result<()> {
     let home_dir_str = std::env::var("HOME")?;
     let home_dir = PathBuf::from(home_dir_str);
-    let (cert, _) = determine_key(&home_dir, key_path)?;
+    let (cert, _) = determine_key(&home_dir, ctx.key_path.clone())?;
 
-    let store_path = get_password_store_path()?;
+    ensure_store_directory_exists(&ctx.store_path)?;
 
-    ensure_store_directory_exists(&store_path)?;
-
-    let password_file_path = get_password_file_path(&store_path, path)?;
+    let password_file_path = get_password_file_path(&ctx.store_path, path)?;
     if !password_file_path.is_file() {
         if password_file_path.is_dir() {
             return Err(eyre!(
@@ -30,16 +40,29 @@ pub fn handle_show(path: &str, show_all: bool, key_path: Option<String>) -> Resu
     }
 
     let encrypted_data = fs::read(&password_file_path)?;
-    let decrypted_data_bytes = decrypt_data(&cert, &encrypted_data)?;
-    let decrypted_content = String::from_utf8(decrypted_data_bytes)?;
+    let decrypted_data_bytes = decrypt_data(&cert, &encrypted_data, &ctx.passphrase)?;
+    let entry = Entry::parse(&String::from_utf8(decrypted_data_bytes)?);
 
-    if show_all {
-        info!("\n{}", decrypted_content);
+    let secret = if let Some(n) = line {
+        entry
+            .line(n)
+            .ok_or_else(|| eyre!("Entry '{}' has no line {}.", path, n))?
+            .to_string()
+    } else if let Some(name) = field {
+        entry
+            .field(name)
+            .ok_or_else(|| eyre!("Entry '{}' has no field '{}'.", path, name))?
+            .to_string()
+    } else if show_all {
+        entry.to_string()
+    } else {
+        entry.password().to_string()
+    };
+    if clip {
+        clip_secret(ctx, path, &secret)?;
     } else {
-        let first_line = decrypted_content.lines().next().unwrap_or("").trim_end();
-        info!("{}", first_line);
+        print_secret(&secret)?;
     }
-    std::io::stdout().flush()?;
 
     Ok(())
 }
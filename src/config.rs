@@ -0,0 +1,229 @@
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item, Table};
+use tracing::debug;
+
+use crate::{
+    constants::{DEFAULT_AGENT_IDLE_TIMEOUT_SECS, DEFAULT_AGENT_TTL_SECS},
+    generator::PasswordPolicy,
+    store::CONFIG_DIR_NAME,
+    strength::StrengthConfig,
+    utils::expand_tilde,
+};
+
+pub const APP_CONFIG_DIR_NAME: &str = "zps";
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// User defaults read from `~/.config/zps/config.toml`.
+/// Every key is optional; anything left out falls back to the built-in default.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Location of the password store.
+    pub store_path: Option<PathBuf>,
+    /// PGP secret key file used to encrypt and decrypt entries.
+    pub key_path: Option<PathBuf>,
+    /// Editor command used by `edit`, preferred over `$EDITOR`.
+    pub editor: Option<String>,
+    /// File whose first line is the password of the secret key.
+    pub passphrase_file: Option<PathBuf>,
+    /// Program printing the password of the secret key, like ssh-askpass.
+    pub askpass: Option<String>,
+    /// Seconds before a copied secret is cleared from the clipboard (0 keeps it).
+    pub clipboard_timeout: Option<u64>,
+    /// Clipboard tool (`wl-copy`, `xclip`, `xsel`, `pbcopy`) or a shell command
+    /// reading the new contents from stdin. Detected from the display server if unset.
+    pub clipboard_command: Option<String>,
+    /// Shell command printing the clipboard, used with a custom `clipboard_command`
+    /// to restore the previous contents. Without it the clipboard is cleared.
+    pub clipboard_paste_command: Option<String>,
+    /// Default policy for generated passwords.
+    pub generator: PasswordPolicy,
+    /// Minimum strength of passwords added to the store.
+    pub strength: StrengthConfig,
+    pub vcs: VcsConfig,
+    pub agent: AgentConfig,
+    /// Profile used when `--profile` is not given.
+    pub default_profile: Option<String>,
+    /// Named stores selectable with `--profile`.
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// A named store with its own key and VCS settings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub store_path: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_path: Option<PathBuf>,
+    /// Overrides the top-level `[vcs]` section for this profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcs: Option<VcsConfig>,
+}
+
+/// Version control behaviour for the store.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VcsConfig {
+    /// Record a Jujutsu commit after every change to the store.
+    pub auto_commit: bool,
+}
+
+impl Default for VcsConfig {
+    fn default() -> Self {
+        Self { auto_commit: true }
+    }
+}
+
+/// Lifetime of keys cached by `zps agent`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentConfig {
+    /// Seconds a key stays cached after it was unlocked.
+    pub ttl: u64,
+    /// Seconds a cached key may go unused before it is wiped.
+    pub idle_timeout: u64,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            ttl: DEFAULT_AGENT_TTL_SECS,
+            idle_timeout: DEFAULT_AGENT_IDLE_TIMEOUT_SECS,
+        }
+    }
+}
+
+/// Returns the default config file path,
+/// `$XDG_CONFIG_HOME/zps/config.toml` or `$HOME/.config/zps/config.toml`.
+pub fn get_config_file_path() -> Result<PathBuf> {
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var("HOME")
+                .map_err(|e| eyre!("Failed to determine home directory (HOME not set): {}", e))?;
+            PathBuf::from(home).join(CONFIG_DIR_NAME)
+        }
+    };
+    Ok(config_home.join(APP_CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+}
+
+impl Config {
+    /// Loads the config from `path`, or from the default location if `None`.
+    /// A missing file at the default location yields the default config,
+    /// while an explicitly requested file must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let (config_path, required) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => (get_config_file_path()?, false),
+        };
+
+        if !config_path.exists() {
+            if required {
+                return Err(eyre!("Config file not found: {:?}", config_path));
+            }
+            debug!("No config file at {:?}, using defaults.", config_path);
+            return Ok(Self::default());
+        }
+
+        let raw = fs::read_to_string(&config_path)?;
+        let mut config: Config = toml::from_str(&raw)
+            .map_err(|e| eyre!("Failed to parse config file {:?}: {}", config_path, e))?;
+        config.store_path = config.store_path.map(|p| expand_tilde(&p));
+        config.key_path = config.key_path.map(|p| expand_tilde(&p));
+        config.passphrase_file = config.passphrase_file.map(|p| expand_tilde(&p));
+        for profile in config.profiles.values_mut() {
+            profile.store_path = expand_tilde(&profile.store_path);
+            profile.key_path = profile.key_path.as_deref().map(expand_tilde);
+        }
+        debug!("Loaded config from {:?}", config_path);
+
+        Ok(config)
+    }
+
+    /// Looks up a profile by name.
+    pub fn profile(&self, name: &str) -> Result<&ProfileConfig> {
+        self.profiles.get(name).ok_or_else(|| {
+            eyre!(
+                "Profile '{}' not found. Use 'zps profile list' to see available profiles.",
+                name
+            )
+        })
+    }
+}
+
+/// Reads the config file at `config_path` as an editable TOML document, so that
+/// profile edits keep every other setting, comment and blank line as the user wrote it.
+fn read_config_document(config_path: &Path) -> Result<DocumentMut> {
+    if !config_path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    let raw = fs::read_to_string(config_path)?;
+    raw.parse::<DocumentMut>()
+        .map_err(|e| eyre!("Failed to parse config file {:?}: {}", config_path, e))
+}
+
+fn write_config_document(config_path: &Path, document: &DocumentMut) -> Result<()> {
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(config_path, document.to_string())?;
+    Ok(())
+}
+
+/// Adds a profile to the config file, creating the file if needed.
+pub fn add_profile(config_path: &Path, name: &str, profile: &ProfileConfig) -> Result<()> {
+    let mut document = read_config_document(config_path)?;
+    let profiles = document
+        .entry("profiles")
+        .or_insert_with(|| {
+            let mut profiles = Table::new();
+            profiles.set_implicit(true);
+            Item::Table(profiles)
+        })
+        .as_table_mut()
+        .ok_or_else(|| eyre!("'profiles' in {:?} is not a table.", config_path))?;
+
+    if profiles.contains_key(name) {
+        return Err(eyre!(
+            "Profile '{}' already exists in {:?}.",
+            name,
+            config_path
+        ));
+    }
+    let serialized = toml::to_string(profile)
+        .map_err(|e| eyre!("Failed to serialize profile '{}': {}", name, e))?;
+    let value = serialized
+        .parse::<DocumentMut>()
+        .map_err(|e| eyre!("Failed to serialize profile '{}': {}", name, e))?
+        .as_table()
+        .clone();
+    profiles.insert(name, Item::Table(value));
+
+    write_config_document(config_path, &document)
+}
+
+/// Removes a profile from the config file.
+/// Clears `default_profile` as well if it pointed at the removed profile.
+pub fn remove_profile(config_path: &Path, name: &str) -> Result<()> {
+    let mut document = read_config_document(config_path)?;
+    let removed = document
+        .get_mut("profiles")
+        .and_then(Item::as_table_like_mut)
+        .and_then(|profiles| profiles.remove(name));
+    if removed.is_none() {
+        return Err(eyre!("Profile '{}' not found in {:?}.", name, config_path));
+    }
+
+    if document.get("default_profile").and_then(Item::as_str) == Some(name) {
+        document.remove("default_profile");
+    }
+
+    write_config_document(config_path, &document)
+}
@@ -1 +1,6 @@
 pub const DEFAULT_PASSWORD_LENGTH: usize = 12;
+pub const DEFAULT_AGENT_TTL_SECS: u64 = 60 * 60;
+pub const DEFAULT_AGENT_IDLE_TIMEOUT_SECS: u64 = 10 * 60;
+pub const MAX_PASSWORD_ATTEMPTS: u32 = 3;
+pub const DEFAULT_CLIPBOARD_TIMEOUT_SECS: u64 = 45;
+pub const DEFAULT_MIN_PASSWORD_SCORE: u8 = 2;
@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+use sequoia_openpgp::Cert;
+use tracing::info;
+
+use crate::{
+    clipboard::Clipboard, config::Config, constants::DEFAULT_CLIPBOARD_TIMEOUT_SECS,
+    generator::PasswordPolicy, gpg::Keyring, passphrase::PassphraseSource,
+    store::resolve_password_store_path, strength::StrengthConfig, vcs::jj_commit_changes,
+};
+
+/// Settings for a single invocation, merged from CLI flags,
+/// environment variables and the config file.
+pub struct Context {
+    pub store_path: PathBuf,
+    pub key_path: Option<String>,
+    pub editor: Option<String>,
+    pub generator: PasswordPolicy,
+    pub strength: StrengthConfig,
+    pub auto_commit: bool,
+    pub config: Config,
+    pub config_path: PathBuf,
+    /// Where the password of the secret key is read from.
+    pub passphrase: PassphraseSource,
+    /// Seconds before a copied secret is cleared from the clipboard.
+    pub clipboard_timeout: u64,
+}
+
+impl Context {
+    /// Resolves the settings for `profile` (or the configured default profile).
+    ///
+    /// An explicitly selected profile takes effect like `--store`, so it wins over
+    /// the store environment variables; the `default_profile` only replaces the
+    /// config file's `store_path`. Key path and VCS settings of the profile
+    /// override the top-level config, and flags override both.
+    pub fn resolve(
+        config: Config,
+        config_path: PathBuf,
+        profile: Option<&str>,
+        store_flag: Option<&Path>,
+        key_path_flag: Option<String>,
+    ) -> Result<Self> {
+        let explicit_profile = profile.is_some();
+        let profile_name = profile
+            .map(str::to_string)
+            .or_else(|| config.default_profile.clone());
+        let active_profile = profile_name
+            .as_deref()
+            .map(|name| config.profile(name))
+            .transpose()?;
+
+        let (cli_store, config_store) = match active_profile {
+            Some(p) if explicit_profile => (
+                store_flag.or(Some(p.store_path.as_path())),
+                config.store_path.as_deref(),
+            ),
+            Some(p) => (store_flag, Some(p.store_path.as_path())),
+            None => (store_flag, config.store_path.as_deref()),
+        };
+        let store_path = resolve_password_store_path(cli_store, config_store)?;
+
+        let key_path = key_path_flag.or_else(|| {
+            active_profile
+                .and_then(|p| p.key_path.as_ref())
+                .or(config.key_path.as_ref())
+                .map(|p| p.to_string_lossy().into_owned())
+        });
+        let vcs = active_profile
+            .and_then(|p| p.vcs.clone())
+            .unwrap_or_else(|| config.vcs.clone());
+
+        Ok(Self {
+            store_path,
+            key_path,
+            editor: config.editor.clone(),
+            generator: config.generator.clone(),
+            strength: config.strength.clone(),
+            auto_commit: vcs.auto_commit,
+            passphrase: PassphraseSource::resolve(
+                None,
+                config.passphrase_file.clone(),
+                config.askpass.clone(),
+            ),
+            clipboard_timeout: config
+                .clipboard_timeout
+                .unwrap_or(DEFAULT_CLIPBOARD_TIMEOUT_SECS),
+            config,
+            config_path,
+        })
+    }
+
+    /// Resolves the clipboard tool from the config.
+    pub fn clipboard(&self) -> Result<Clipboard> {
+        Clipboard::resolve(
+            self.config.clipboard_command.as_deref(),
+            self.config.clipboard_paste_command.as_deref(),
+        )
+    }
+
+    /// Creates a keyring for `cert` that reads its password from this invocation's source.
+    pub fn keyring(&self, cert: Cert) -> Keyring {
+        let passphrase = self.passphrase.clone();
+        Keyring::new(cert, move || passphrase.read())
+            .with_password_attempts(self.passphrase.attempts())
+    }
+
+    /// Resolves the context of another store for commands operating on two stores,
+    /// selected by profile name or by store path. A bare store path keeps this
+    /// invocation's key.
+    pub fn for_target(&self, profile: Option<&str>, store: Option<&Path>) -> Result<Self> {
+        let key_path = if profile.is_some() {
+            None
+        } else {
+            self.key_path.clone()
+        };
+        let mut target = Self::resolve(
+            self.config.clone(),
+            self.config_path.clone(),
+            profile,
+            store,
+            key_path,
+        )?;
+        target.passphrase = self.passphrase.clone();
+        Ok(target)
+    }
+
+    /// Records a commit in the store unless auto-commit is disabled.
+    pub fn commit(&self, message: &str) -> Result<()> {
+        if !self.auto_commit {
+            info!("Auto-commit disabled, not committing: {}", message);
+            return Ok(());
+        }
+        jj_commit_changes(&self.store_path, message)
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+
+const OTPAUTH_SCHEME: &str = "otpauth://";
+
+/// A line after the password, classified by its content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryLine {
+    /// A `key: value` line.
+    Field(Field),
+    /// An `otpauth://` URI.
+    Otpauth(String),
+    /// Anything else, kept as free-form notes.
+    Note(String),
+}
+
+/// A `key: value` metadata line. The original spelling is kept so the
+/// entry is written back exactly as it was read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub key: String,
+    pub value: String,
+    raw: String,
+}
+
+impl Field {
+    /// Creates a field, written as `key: value`.
+    pub fn new(key: &str, value: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            value: value.to_string(),
+            raw: format!("{}: {}", key, value),
+        }
+    }
+
+    /// Parses a `key: value` line. The key must be non-empty and may contain
+    /// spaces, but not start or end with whitespace, and the colon must be
+    /// followed by a space or the end of the line, so URIs like `otpauth://...`
+    /// are not mistaken for fields.
+    fn parse(line: &str) -> Option<Self> {
+        let (key, value) = line.split_once(':')?;
+        if key.is_empty()
+            || key.trim() != key
+            || !(value.is_empty() || value.starts_with(' ') || value == "\r")
+        {
+            return None;
+        }
+        Some(Self {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+            raw: line.to_string(),
+        })
+    }
+}
+
+impl EntryLine {
+    fn parse(line: &str) -> Self {
+        let trimmed = line.trim();
+        if trimmed.starts_with(OTPAUTH_SCHEME) {
+            EntryLine::Otpauth(line.to_string())
+        } else if let Some(field) = Field::parse(line) {
+            EntryLine::Field(field)
+        } else {
+            EntryLine::Note(line.to_string())
+        }
+    }
+
+    fn raw(&self) -> &str {
+        match self {
+            EntryLine::Field(field) => &field.raw,
+            EntryLine::Otpauth(raw) | EntryLine::Note(raw) => raw,
+        }
+    }
+}
+
+/// The decrypted content of a password entry: the password on the first
+/// line, followed by metadata fields, otpauth URIs and notes.
+/// `Entry::parse(text).to_string() == text` for any text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    password: String,
+    lines: Vec<EntryLine>,
+    trailing_newline: bool,
+}
+
+impl Entry {
+    /// Parses the decrypted text of an entry.
+    pub fn parse(text: &str) -> Self {
+        let (text, trailing_newline) = match text.strip_suffix('\n') {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+        let mut lines = text.split('\n');
+        let password = lines.next().unwrap_or_default().to_string();
+        Self {
+            password,
+            lines: lines.map(EntryLine::parse).collect(),
+            trailing_newline,
+        }
+    }
+
+    /// Replaces the password, keeping the rest of the entry.
+    pub fn set_password(&mut self, password: &str) {
+        let line_ending = if self.password.ends_with('\r') {
+            "\r"
+        } else {
+            ""
+        };
+        self.password = format!("{}{}", password, line_ending);
+    }
+
+    /// Appends a metadata field after the existing lines.
+    pub fn push_field(&mut self, field: Field) {
+        self.lines.push(EntryLine::Field(field));
+    }
+
+    /// The password, without trailing whitespace.
+    pub fn password(&self) -> &str {
+        self.password.trim_end()
+    }
+
+    /// Whether the entry contains nothing but whitespace.
+    pub fn is_empty(&self) -> bool {
+        self.password.trim().is_empty() && self.lines.iter().all(|l| l.raw().trim().is_empty())
+    }
+
+    /// All lines including the password, without line endings.
+    pub fn lines(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.password.as_str())
+            .chain(self.lines.iter().map(EntryLine::raw))
+            .map(|line| line.strip_suffix('\r').unwrap_or(line))
+    }
+
+    /// Line `n`, counting the password as line 1.
+    pub fn line(&self, n: usize) -> Option<&str> {
+        self.lines().nth(n.checked_sub(1)?).map(str::trim_end)
+    }
+
+    /// The metadata fields in order.
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.lines.iter().filter_map(|line| match line {
+            EntryLine::Field(field) => Some(field),
+            _ => None,
+        })
+    }
+
+    /// The value of the first field named `name`, compared case-insensitively.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields()
+            .find(|field| field.key.eq_ignore_ascii_case(name))
+            .map(|field| field.value.as_str())
+    }
+
+    /// The first otpauth URI, which may also be stored in place of the password.
+    pub fn otpauth(&self) -> Option<&str> {
+        let password = self.password().trim_start();
+        if password.starts_with(OTPAUTH_SCHEME) {
+            return Some(password);
+        }
+        self.lines.iter().find_map(|line| match line {
+            EntryLine::Otpauth(uri) => Some(uri.trim()),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.password)?;
+        for line in &self.lines {
+            write!(f, "\n{}", line.raw())?;
+        }
+        if self.trailing_newline {
+            f.write_str("\n")?;
+        }
+        Ok(())
+    }
+}
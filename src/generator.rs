@@ -0,0 +1,350 @@
+use eyre::{Result, eyre};
+use rand::{
+    Rng, rng,
+    seq::{IndexedRandom, SliceRandom},
+};
+use serde::{Deserialize, Serialize};
+use std::iter;
+
+use crate::constants::DEFAULT_PASSWORD_LENGTH;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!@#$%^&*()_+-=[]{}|;:',.<>/?";
+/// Characters easily mistaken for one another when read or typed.
+const AMBIGUOUS: &str = "0O1lI|";
+
+/// Bundled diceware wordlist, one `<dice rolls>\t<word>` pair per line.
+/// Derived from the EFF short wordlist, see `assets/README.md`.
+const WORDLIST: &str = include_str!("../assets/wordlist.txt");
+const DEFAULT_SEPARATOR: &str = "-";
+
+/// Rules for generated passwords, read from `[generator]` in the config
+/// and from recipes in the store.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    /// Whether symbols are part of the character set.
+    pub symbols: bool,
+    pub min_lowercase: usize,
+    pub min_uppercase: usize,
+    pub min_digits: usize,
+    pub min_symbols: usize,
+    /// Extra characters added to the character set.
+    pub allowed: String,
+    /// Characters never used, even if part of an included class.
+    pub forbidden: String,
+    /// Leave out characters like `0`/`O` and `1`/`l`.
+    pub exclude_ambiguous: bool,
+    /// Generate a passphrase of this many words instead of random characters.
+    pub words: Option<usize>,
+    /// Put between the words of a passphrase.
+    pub separator: String,
+    /// Start every word of a passphrase with an uppercase letter.
+    pub capitalize: bool,
+    /// End a passphrase with a random digit.
+    pub append_digit: bool,
+    /// End a passphrase with a random symbol.
+    pub append_symbol: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            length: DEFAULT_PASSWORD_LENGTH,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            min_lowercase: 0,
+            min_uppercase: 0,
+            min_digits: 0,
+            min_symbols: 0,
+            allowed: String::new(),
+            forbidden: String::new(),
+            exclude_ambiguous: false,
+            words: None,
+            separator: DEFAULT_SEPARATOR.to_string(),
+            capitalize: false,
+            append_digit: false,
+            append_symbol: false,
+        }
+    }
+}
+
+/// A character class with its usable characters and required count.
+struct CharClass {
+    name: &'static str,
+    included: bool,
+    chars: Vec<char>,
+    min: usize,
+}
+
+impl PasswordPolicy {
+    fn is_usable(&self, c: char) -> bool {
+        let ambiguous = self.exclude_ambiguous && AMBIGUOUS.contains(c);
+        !ambiguous && !self.forbidden.contains(c)
+    }
+
+    fn classes(&self) -> [CharClass; 4] {
+        let class = |name, set: &str, included, min| CharClass {
+            name,
+            included,
+            chars: set.chars().filter(|&c| self.is_usable(c)).collect(),
+            min,
+        };
+        [
+            class("lowercase", LOWERCASE, self.lowercase, self.min_lowercase),
+            class("uppercase", UPPERCASE, self.uppercase, self.min_uppercase),
+            class("digits", DIGITS, self.digits, self.min_digits),
+            class("symbols", SYMBOLS, self.symbols, self.min_symbols),
+        ]
+    }
+
+    /// The characters passwords are drawn from, each listed once.
+    pub fn pool(&self) -> Vec<char> {
+        let mut pool: Vec<char> = Vec::new();
+        let included = self
+            .classes()
+            .into_iter()
+            .filter(|class| class.included)
+            .flat_map(|class| class.chars);
+        for c in included.chain(self.allowed.chars().filter(|&c| self.is_usable(c))) {
+            if !pool.contains(&c) {
+                pool.push(c);
+            }
+        }
+        pool
+    }
+
+    /// Checks that the policy can be met at all.
+    pub fn validate(&self) -> Result<()> {
+        if self.length == 0 {
+            return Err(eyre!("Password length cannot be zero."));
+        }
+        let mut required = 0;
+        for class in self.classes() {
+            if class.min == 0 {
+                continue;
+            }
+            if !class.included {
+                return Err(eyre!(
+                    "A minimum of {} is set for {}, but they are excluded.",
+                    class.min,
+                    class.name
+                ));
+            }
+            if class.chars.is_empty() {
+                return Err(eyre!(
+                    "A minimum of {} is set for {}, but all of them are forbidden.",
+                    class.min,
+                    class.name
+                ));
+            }
+            required += class.min;
+        }
+        if required > self.length {
+            return Err(eyre!(
+                "The minimum counts add up to {} characters, more than the length of {}.",
+                required,
+                self.length
+            ));
+        }
+        if self.pool().is_empty() {
+            return Err(eyre!("The policy leaves no characters to choose from."));
+        }
+        Ok(())
+    }
+
+    /// Estimated entropy in bits of passwords generated with this policy.
+    /// Minimum counts are not accounted for, so strict policies score a bit high.
+    pub fn entropy_bits(&self) -> f64 {
+        if let Some(words) = self.words {
+            let appended: f64 = [(self.append_digit, DIGITS), (self.append_symbol, SYMBOLS)]
+                .into_iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, set)| {
+                    let usable = set.chars().filter(|&c| self.is_usable(c)).count();
+                    (usable.max(1) as f64).log2()
+                })
+                .sum();
+            return words as f64 * (wordlist().len() as f64).log2() + appended;
+        }
+        self.length as f64 * (self.pool().len().max(1) as f64).log2()
+    }
+}
+
+/// The words of the bundled diceware wordlist.
+pub fn wordlist() -> Vec<&'static str> {
+    WORDLIST
+        .lines()
+        .filter_map(|line| line.split_whitespace().last())
+        .collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Generates a passphrase of `words` words drawn uniformly from the bundled
+/// wordlist, with the digit and symbol requested by `policy` appended.
+fn generate_passphrase(policy: &PasswordPolicy, words: usize) -> Result<String> {
+    if words == 0 {
+        return Err(eyre!("Passphrase word count cannot be zero."));
+    }
+    let wordlist = wordlist();
+    let mut rng = rng();
+
+    let mut passphrase = (0..words)
+        .filter_map(|_| wordlist.choose(&mut rng))
+        .map(|word| {
+            if policy.capitalize {
+                capitalize(word)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&policy.separator);
+
+    let appended = [
+        (policy.append_digit, "digits", DIGITS),
+        (policy.append_symbol, "symbols", SYMBOLS),
+    ];
+    for (enabled, name, set) in appended {
+        if !enabled {
+            continue;
+        }
+        let chars: Vec<char> = set.chars().filter(|&c| policy.is_usable(c)).collect();
+        let c = chars.choose(&mut rng).ok_or_else(|| {
+            eyre!(
+                "Cannot append one of the {}, all of them are forbidden.",
+                name
+            )
+        })?;
+        passphrase.push(*c);
+    }
+    Ok(passphrase)
+}
+
+/// Adds probabilities given as natural logarithms, without leaving log space.
+fn ln_add(a: f64, b: f64) -> f64 {
+    let max = a.max(b);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + ((a - max).exp() + (b - max).exp()).ln()
+}
+
+/// Draws how many of `length` characters come from each required class, given
+/// as `(size, min)`, with the rest from `rest_size` other characters. Each split
+/// is weighted by how many passwords have it, so the passwords built from the
+/// counts are uniform over all those meeting the minimums. The last count is
+/// for the other characters.
+fn sample_class_counts(
+    length: usize,
+    required: &[(usize, usize)],
+    rest_size: usize,
+    rng: &mut impl Rng,
+) -> Result<Vec<usize>> {
+    let mut ln_factorial = vec![0.0; length + 1];
+    for n in 1..=length {
+        ln_factorial[n] = ln_factorial[n - 1] + (n as f64).ln();
+    }
+    let ln_choose = |n: usize, k: usize| ln_factorial[n] - ln_factorial[k] - ln_factorial[n - k];
+
+    // ln_ways[j][n]: the number of ways to fill n positions from classes j.. and the rest.
+    let mut ln_ways = vec![vec![f64::NEG_INFINITY; length + 1]; required.len() + 1];
+    for (n, ways) in ln_ways[required.len()].iter_mut().enumerate() {
+        *ways = match (n, rest_size) {
+            (0, _) => 0.0,
+            (_, 0) => f64::NEG_INFINITY,
+            _ => n as f64 * (rest_size as f64).ln(),
+        };
+    }
+    for (j, &(size, min)) in required.iter().enumerate().rev() {
+        for n in min..=length {
+            ln_ways[j][n] = (min..=n)
+                .map(|k| ln_choose(n, k) + k as f64 * (size as f64).ln() + ln_ways[j + 1][n - k])
+                .fold(f64::NEG_INFINITY, ln_add);
+        }
+    }
+
+    let mut counts = Vec::with_capacity(required.len() + 1);
+    let mut remaining = length;
+    for (j, &(size, min)) in required.iter().enumerate() {
+        let total = ln_ways[j][remaining];
+        if total == f64::NEG_INFINITY {
+            return Err(eyre!("The policy cannot be met."));
+        }
+        let mut target = rng.random::<f64>();
+        let mut count = min;
+        for k in min..=remaining {
+            count = k;
+            let weight = ln_choose(remaining, k)
+                + k as f64 * (size as f64).ln()
+                + ln_ways[j + 1][remaining - k];
+            target -= (weight - total).exp();
+            if target < 0.0 {
+                break;
+            }
+        }
+        counts.push(count);
+        remaining -= count;
+    }
+    counts.push(remaining);
+    Ok(counts)
+}
+
+/// Generates a password following `policy`, or a passphrase if it asks for words.
+///
+/// Every password meeting the policy is equally likely: the number of
+/// characters from each required class is drawn with the weight of how many
+/// passwords have it, then the positions are shuffled and each character is
+/// drawn uniformly from its class. Forcing required characters into fixed
+/// places instead would make those positions predictable.
+pub fn generate_password(policy: &PasswordPolicy) -> Result<String> {
+    if let Some(words) = policy.words {
+        return generate_passphrase(policy, words);
+    }
+    policy.validate()?;
+    let pool = policy.pool();
+    let mut rng = rng();
+
+    let required: Vec<CharClass> = policy
+        .classes()
+        .into_iter()
+        .filter(|class| class.min > 0)
+        .collect();
+    let rest: Vec<char> = pool
+        .iter()
+        .copied()
+        .filter(|c| !required.iter().any(|class| class.chars.contains(c)))
+        .collect();
+    let sizes: Vec<(usize, usize)> = required
+        .iter()
+        .map(|class| (class.chars.len(), class.min))
+        .collect();
+    let counts = sample_class_counts(policy.length, &sizes, rest.len(), &mut rng)?;
+
+    let mut slots: Vec<&[char]> = Vec::with_capacity(policy.length);
+    let groups = required.iter().map(|class| class.chars.as_slice());
+    for (group, count) in groups.chain(iter::once(rest.as_slice())).zip(counts) {
+        slots.extend(iter::repeat_n(group, count));
+    }
+    slots.shuffle(&mut rng);
+
+    Ok(slots
+        .iter()
+        .filter_map(|group| group.choose(&mut rng))
+        .collect())
+}
@@ -1,22 +1,33 @@
 use eyre::{Result, eyre};
 use sequoia_openpgp::{
-    Cert, KeyHandle, KeyID,
-    cert::CertBuilder,
-    crypto::SessionKey,
+    Cert, KeyHandle, KeyID, Packet,
+    cert::{CertBuilder, amalgamation::key::ValidErasedKeyAmalgamation},
+    crypto::{KeyPair, Password, SessionKey},
     packet::{
         Key, PKESK, SKESK,
-        key::{SecretParts, UnspecifiedRole},
+        key::{PublicParts, SecretParts, UnspecifiedRole},
     },
     parse::{
-        Parse,
+        PacketParser, PacketParserResult, Parse,
         stream::{DecryptionHelper, DecryptorBuilder, MessageStructure, VerificationHelper},
     },
     policy::StandardPolicy,
     serialize::stream::{Armorer, Encryptor, LiteralWriter, Message},
-    types::SymmetricAlgorithm,
+    types::{RevocationStatus, SymmetricAlgorithm},
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashMap},
+    fmt,
+    io::Write,
+    path::Path,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    agent::{agent_cache_key, agent_decrypt},
+    passphrase::PassphraseSource,
 };
-use std::{collections::HashMap, io::Write, path::Path};
-use tracing::info;
 
 /// Generates a new OpenPGP key with an encryption subkey,
 /// protected by the given password.
@@ -81,18 +92,90 @@ pub fn load_and_validate_key_from_file(key_file_path: &Path) -> Result<Cert> {
     Ok(cert)
 }
 
-/// Encrypts data for the recipient identified by the Cert.
-/// The `recipient_cert` is the public key we loaded earlier.
-pub fn encrypt_data(data: &[u8], recipient: &Cert) -> Result<Vec<u8>> {
+/// Checks that entries can still be encrypted to `cert`: it is neither
+/// expired nor revoked and has a live storage encryption subkey.
+pub fn check_recipient_cert(cert: &Cert) -> Result<()> {
     let p = StandardPolicy::new();
+    let fingerprint = cert.fingerprint().to_hex().to_uppercase();
 
-    let recipients = recipient
-        .keys()
+    let valid_cert = cert
         .with_policy(&p, None)
-        .supported()
+        .map_err(|e| eyre!("Key '{}' is not valid: {}", fingerprint, e))?;
+    valid_cert
         .alive()
-        .revoked(false)
-        .for_storage_encryption();
+        .map_err(|e| eyre!("Key '{}' is expired: {}", fingerprint, e))?;
+    if let RevocationStatus::Revoked(_) = valid_cert.revocation_status() {
+        return Err(eyre!("Key '{}' has been revoked", fingerprint));
+    }
+    if encryption_keys(std::slice::from_ref(cert), &p).is_empty() {
+        return Err(eyre!("Key '{}' has no live encryption subkey", fingerprint));
+    }
+    Ok(())
+}
+
+/// Selects the subkeys of `recipients` that entries are encrypted to.
+fn encryption_keys<'a>(
+    recipients: &'a [Cert],
+    policy: &'a StandardPolicy,
+) -> Vec<ValidErasedKeyAmalgamation<'a, PublicParts>> {
+    recipients
+        .iter()
+        .flat_map(|recipient| {
+            recipient
+                .keys()
+                .with_policy(policy, None)
+                .supported()
+                .alive()
+                .revoked(false)
+                .for_storage_encryption()
+        })
+        .collect()
+}
+
+/// Returns the key IDs `encrypt_data` would encrypt to for `recipients`.
+pub fn encryption_key_ids(recipients: &[Cert]) -> BTreeSet<KeyID> {
+    let p = StandardPolicy::new();
+    encryption_keys(recipients, &p)
+        .iter()
+        .map(|ka| ka.key().keyid())
+        .collect()
+}
+
+/// Returns the key IDs an encrypted message was encrypted to,
+/// read from its PKESK packets without decrypting anything.
+pub fn message_recipient_key_ids(ciphertext: &[u8]) -> Result<BTreeSet<KeyID>> {
+    let mut key_ids = BTreeSet::new();
+    let mut ppr = PacketParser::from_bytes(ciphertext)
+        .map_err(|e| eyre!("Failed to parse encrypted message: {}", e))?;
+
+    while let PacketParserResult::Some(pp) = ppr {
+        match &pp.packet {
+            Packet::PKESK(pkesk) => {
+                key_ids.insert(KeyID::from(pkesk.recipient()));
+            }
+            Packet::SKESK(_) => {}
+            _ => break,
+        }
+        let (_, next) = pp
+            .next()
+            .map_err(|e| eyre!("Failed to parse encrypted message: {}", e))?;
+        ppr = next;
+    }
+
+    Ok(key_ids)
+}
+
+/// Encrypts data for every recipient in `recipients`,
+/// so that any of their secret keys can decrypt it.
+pub fn encrypt_data(data: &[u8], recipients: &[Cert]) -> Result<Vec<u8>> {
+    let p = StandardPolicy::new();
+
+    let recipients = encryption_keys(recipients, &p);
+    if recipients.is_empty() {
+        return Err(eyre!(
+            "No valid encryption-capable keys among the recipients."
+        ));
+    }
 
     let mut sink = Vec::new();
 
@@ -118,66 +201,226 @@ pub fn encrypt_data(data: &[u8], recipient: &Cert) -> Result<Vec<u8>> {
 }
 
 /// Decrypts the given armored ciphertext using the recipient's TSK.
-/// Prompts for password if the TSK is encrypted.
-pub fn decrypt_data(recipient: &Cert, ciphertext: &[u8]) -> Result<Vec<u8>> {
-    let p = &StandardPolicy::new();
-    let mut decrypted_plaintext = Vec::new();
-
-    let helper = Helper::new(recipient, || {
-        rpassword::prompt_password("Enter password for PGP key: ")
-            .map_err(|e| eyre!("Failed to read password: {}", e))
-    })?;
-
-    let mut decryptor = DecryptorBuilder::from_bytes(ciphertext)
-        .map_err(|e| eyre!(e))?
-        .with_policy(p, None, helper)
-        .map_err(|e| eyre!("Failed to build decryptor: {}", e))?;
+/// Reads the password from `passphrase` if the subkey the message is
+/// encrypted to is protected.
+pub fn decrypt_data(
+    recipient: &Cert,
+    ciphertext: &[u8],
+    passphrase: &PassphraseSource,
+) -> Result<Vec<u8>> {
+    let source = passphrase.clone();
+    Keyring::new(recipient.clone(), move || source.read())
+        .with_password_attempts(passphrase.attempts())
+        .decrypt(ciphertext)
+}
 
-    std::io::copy(&mut decryptor, &mut decrypted_plaintext)?;
+/// Returned when every password attempt for a subkey was wrong,
+/// so bulk operations can stop instead of failing entry by entry.
+#[derive(Debug)]
+pub struct WrongPassword {
+    pub key_id: KeyID,
+    pub attempts: u32,
+}
 
-    Ok(decrypted_plaintext)
+impl fmt::Display for WrongPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Wrong password for key {} ({} attempt(s)).",
+            self.key_id, self.attempts
+        )
+    }
 }
 
-struct Helper {
-    secret_keys: HashMap<KeyID, (Cert, Key<SecretParts, UnspecifiedRole>)>,
-    //key_identities: HashMap<KeyID, Arc<Cert>>,
-    password: String,
+impl std::error::Error for WrongPassword {}
+
+/// The user's TSK, whose decryption subkeys are unlocked when a message
+/// encrypted to them is first seen and kept for the rest of the process,
+/// so bulk operations prompt at most once.
+pub struct Keyring {
+    cert: Cert,
+    password_cb: Box<dyn Fn() -> Result<String> + Send>,
+    password_attempts: u32,
+    /// The password that unlocked a subkey, tried first for the others.
+    password: RefCell<Option<Password>>,
+    keypairs: RefCell<HashMap<KeyID, KeyPair>>,
 }
 
-impl Helper {
-    /// Creates a new helper.
-    /// `password_cb` is a function that will be called to get the password if a key is encrypted.
-    fn new(secret: &Cert, password_cb: impl Fn() -> Result<String>) -> Result<Self> {
-        let p = StandardPolicy::new();
+impl Keyring {
+    /// Creates a locked keyring for `secret`. `password_cb` is only called
+    /// when a message needs a password-protected subkey.
+    pub fn new(secret: Cert, password_cb: impl Fn() -> Result<String> + Send + 'static) -> Self {
+        Self {
+            cert: secret,
+            password_cb: Box::new(password_cb),
+            password_attempts: 1,
+            password: RefCell::new(None),
+            keypairs: RefCell::new(HashMap::new()),
+        }
+    }
 
-        let mut keys = HashMap::new();
-        //let mut identities = HashMap::new();
+    /// Sets how often `password_cb` is asked again after a wrong password.
+    pub fn with_password_attempts(mut self, attempts: u32) -> Self {
+        self.password_attempts = attempts.max(1);
+        self
+    }
+
+    /// Creates a keyring for `secret` and unlocks all its decryption subkeys
+    /// right away with `password`.
+    pub fn unlocked(secret: Cert, password: Password) -> Result<Self> {
+        let keyring = Self::new(secret, || Err(eyre!("No password available.")));
+        *keyring.password.borrow_mut() = Some(password);
+        for key_id in keyring.decryption_key_ids() {
+            keyring.keypair(&key_id)?;
+        }
+        Ok(keyring)
+    }
 
-        //let cert = Arc::new(secret.clone());
-        for ka in secret
+    /// The certificate the keyring unlocks.
+    pub fn cert(&self) -> &Cert {
+        &self.cert
+    }
+
+    /// Whether no subkey has been unlocked yet.
+    fn is_locked(&self) -> bool {
+        self.keypairs.borrow().is_empty()
+    }
+
+    fn decryption_keys(&self) -> Vec<Key<SecretParts, UnspecifiedRole>> {
+        let p = StandardPolicy::new();
+        self.cert
             .keys()
             .secret()
             .with_policy(&p, None)
             .for_storage_encryption()
             .for_transport_encryption()
+            .map(|ka| ka.key().clone())
+            .collect()
+    }
+
+    fn decryption_key_ids(&self) -> Vec<KeyID> {
+        self.decryption_keys()
+            .iter()
+            .map(|key| key.keyid())
+            .collect()
+    }
+
+    /// Returns the key pair of the decryption subkey `key_id`, unlocking it
+    /// first if needed. `None` if the TSK has no such secret subkey.
+    fn keypair(&self, key_id: &KeyID) -> Result<Option<KeyPair>> {
+        if let Some(keypair) = self.keypairs.borrow().get(key_id) {
+            return Ok(Some(keypair.clone()));
+        }
+        let Some(key) = self
+            .decryption_keys()
+            .into_iter()
+            .find(|key| &key.keyid() == key_id)
+        else {
+            return Ok(None);
+        };
+
+        let key = if key.has_unencrypted_secret() {
+            key
+        } else {
+            self.unlock_key(key)?
+        };
+        let keypair = key.into_keypair().map_err(|e| eyre!(e))?;
+        self.keypairs
+            .borrow_mut()
+            .insert(key_id.clone(), keypair.clone());
+        Ok(Some(keypair))
+    }
+
+    /// Decrypts the secret of `key`, trying the known password first and then
+    /// asking `password_cb` up to `password_attempts` times.
+    fn unlock_key(
+        &self,
+        key: Key<SecretParts, UnspecifiedRole>,
+    ) -> Result<Key<SecretParts, UnspecifiedRole>> {
+        if let Some(password) = self.password.borrow().as_ref()
+            && let Ok(key) = key.clone().decrypt_secret(password)
         {
-            let id = ka.key().keyid();
-            let key = ka.key();
-            keys.insert(id.clone(), (secret.clone(), key.clone()));
-            //identities.insert(id.clone(), cert.clone());
+            return Ok(key);
         }
 
-        let password = password_cb()?;
+        for attempt in 1..=self.password_attempts {
+            let password = Password::from((self.password_cb)()?);
+            match key.clone().decrypt_secret(&password) {
+                Ok(key) => {
+                    *self.password.borrow_mut() = Some(password);
+                    return Ok(key);
+                }
+                Err(_) if attempt < self.password_attempts => {
+                    warn!(
+                        "Wrong password, please try again ({}/{}).",
+                        attempt, self.password_attempts
+                    );
+                }
+                Err(_) => {}
+            }
+        }
 
-        Ok(Self {
-            secret_keys: keys,
-            //key_identities: identities,
-            password,
-        })
+        Err(WrongPassword {
+            key_id: key.keyid(),
+            attempts: self.password_attempts,
+        }
+        .into())
+    }
+
+    /// Decrypts the given armored ciphertext. While the keyring is locked, a running
+    /// agent holding the key decrypts instead; otherwise the subkey the message is
+    /// encrypted to is unlocked and the key handed to the agent for later invocations.
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        let was_locked = self.is_locked();
+        if was_locked {
+            match agent_decrypt(&self.cert, ciphertext) {
+                Ok(Some(plaintext)) => return Ok(plaintext),
+                Ok(None) => {}
+                Err(e) => debug!("Agent could not decrypt: {}", e),
+            }
+        }
+
+        let p = &StandardPolicy::new();
+        let mut decrypted_plaintext = Vec::new();
+
+        let error = RefCell::new(None);
+        let helper = Helper {
+            keyring: self,
+            error: &error,
+        };
+
+        let mut decryptor = match DecryptorBuilder::from_bytes(ciphertext)
+            .map_err(|e| eyre!(e))?
+            .with_policy(p, None, helper)
+        {
+            Ok(decryptor) => decryptor,
+            Err(e) => {
+                return Err(error
+                    .take()
+                    .unwrap_or_else(|| eyre!("Failed to build decryptor: {}", e)));
+            }
+        };
+
+        std::io::copy(&mut decryptor, &mut decrypted_plaintext)?;
+
+        if was_locked
+            && !self.is_locked()
+            && let Err(e) = agent_cache_key(&self.cert, self.password.borrow().as_ref())
+        {
+            warn!("Failed to cache key in agent: {}", e);
+        }
+
+        Ok(decrypted_plaintext)
     }
 }
 
-impl DecryptionHelper for Helper {
+struct Helper<'a> {
+    keyring: &'a Keyring,
+    /// Why no session key could be recovered, reported instead of sequoia's generic error.
+    error: &'a RefCell<Option<eyre::Report>>,
+}
+
+impl DecryptionHelper for Helper<'_> {
     fn decrypt(
         &mut self,
         pkesks: &[PKESK],
@@ -187,29 +430,33 @@ impl DecryptionHelper for Helper {
     ) -> sequoia_openpgp::anyhow::Result<Option<Cert>> {
         for pkesk in pkesks {
             let keyid = KeyID::from(pkesk.recipient());
-            if let Some((cert, key)) = self.secret_keys.get_mut(&keyid) {
-                if !key.clone().has_unencrypted_secret() {
-                    let password = self.password.clone();
-                    let mut keypair = key
-                        .clone()
-                        .decrypt_secret(&password.into())?
-                        .into_keypair()?;
-
-                    if pkesk
-                        .decrypt(&mut keypair, sym_algo_pref)
-                        .map(|(algo, session_key)| decrypt_to(algo, &session_key))
-                        .unwrap_or(false)
-                    {
-                        return Ok(Some(cert.clone()));
-                    }
+            let mut keypair = match self.keyring.keypair(&keyid) {
+                Ok(Some(keypair)) => keypair,
+                Ok(None) => continue,
+                Err(e) => {
+                    *self.error.borrow_mut() = Some(e);
+                    return Ok(None);
                 }
+            };
+
+            if pkesk
+                .decrypt(&mut keypair, sym_algo_pref)
+                .map(|(algo, session_key)| decrypt_to(algo, &session_key))
+                .unwrap_or(false)
+            {
+                return Ok(Some(self.keyring.cert.clone()));
             }
         }
+
+        *self.error.borrow_mut() = Some(eyre!(
+            "The entry is not encrypted to key {}.",
+            self.keyring.cert.fingerprint().to_hex()
+        ));
         Ok(None)
     }
 }
 
-impl VerificationHelper for Helper {
+impl VerificationHelper for Helper<'_> {
     fn get_certs(&mut self, _ids: &[KeyHandle]) -> sequoia_openpgp::anyhow::Result<Vec<Cert>> {
         Ok(Vec::new())
     }
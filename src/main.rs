@@ -1,67 +1,244 @@
 use clap::Parser;
-use cli::{Cli, Commands, OtpCommands, PassCommands};
+use cli::{
+    AgentCommands, Cli, Commands, OtpCommands, PassCommands, ProfileCommands, RecipientsCommands,
+};
+use clipboard::{Clipboard, restore_clipboard};
 use commands::{
+    agent::{handle_agent_lock, handle_agent_start, handle_agent_status, handle_agent_stop},
+    cp::handle_cp,
     edit::handle_edit,
+    find::handle_find,
+    grep::handle_grep,
     init::handle_init_command,
     list::handle_list,
+    mv::handle_mv,
     otp::{add::handle_otp_add, generate::handle_otp_generate},
-    pass::{add::handle_pass_add, generate::handle_pass_generate},
+    pass::{
+        add::{PasswordInput, handle_pass_add},
+        generate::handle_pass_generate,
+        rotate::handle_pass_rotate,
+    },
+    profile::{handle_profile_add, handle_profile_list, handle_profile_remove},
+    recipients::{handle_recipients_add, handle_recipients_list, handle_recipients_remove},
+    reencrypt::handle_reencrypt,
     remove::handle_remove,
     show::handle_show,
 };
-use eyre::Result;
+use config::Config;
+use eyre::{Result, eyre};
 
+mod agent;
 mod cli;
+mod clipboard;
 mod commands;
+mod config;
 mod constants;
+mod context;
+mod entry;
+mod generator;
 mod gpg;
+mod output;
+mod passphrase;
+mod recipe;
+mod recipients;
 mod store;
+mod strength;
 mod utils;
 mod vcs;
 
 fn main() -> Result<()> {
-    tracing_subscriber::fmt().init();
-
     let cli = Cli::parse();
+    output::init_logging(cli.verbose, cli.quiet);
+
+    // The clipboard restore process is spawned without the parent's flags and
+    // output, so it must not depend on the config to clear the secret.
+    if let Commands::ClipboardRestore {
+        timeout,
+        copy_command,
+        paste_command,
+    } = cli.command
+    {
+        let clipboard = Clipboard {
+            copy_command,
+            paste_command,
+        };
+        return restore_clipboard(&clipboard, timeout);
+    }
+
+    let config = Config::load(cli.config.as_deref())?;
+
+    // Profile commands only edit the config file. They skip resolving the
+    // context so that a missing selected or default profile can still be fixed.
+    let config_path = cli.config_path()?;
+    if let Commands::Profile { command } = cli.command {
+        match command {
+            ProfileCommands::List => {
+                let active_profile = cli.profile.or_else(|| config.default_profile.clone());
+                handle_profile_list(&config, &config_path, active_profile.as_deref())?;
+            }
+            ProfileCommands::Add {
+                name,
+                store,
+                key_path,
+                no_auto_commit,
+            } => {
+                handle_profile_add(&config_path, &name, store, key_path, no_auto_commit)?;
+            }
+            ProfileCommands::Remove { name } => {
+                handle_profile_remove(&config_path, &name)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let ctx = cli.merge_config(config)?;
 
     match cli.command {
         Commands::Init {} => {
-            handle_init_command(cli.key_path)?;
+            handle_init_command(&ctx)?;
         }
         Commands::Pass { command } => match command {
             PassCommands::Add {
                 path,
                 force,
                 generate,
-                length,
-                no_symbols,
+                policy,
+                multiline,
+                echo,
+                no_confirm,
+                fields,
+                allow_weak,
             } => {
-                handle_pass_add(&path, force, generate, length, no_symbols, cli.key_path)?;
+                if !generate && policy.is_set() {
+                    return Err(eyre!("Generator options require --generate."));
+                }
+                let input = if generate {
+                    PasswordInput::Generate(policy)
+                } else if multiline {
+                    PasswordInput::Multiline
+                } else if echo {
+                    PasswordInput::Echo
+                } else {
+                    PasswordInput::Prompt {
+                        confirm: !no_confirm,
+                    }
+                };
+                handle_pass_add(&ctx, &path, force, input, &fields, allow_weak)?;
             }
-            PassCommands::Generate { length, no_symbols } => {
-                handle_pass_generate(length, no_symbols)?;
+            PassCommands::Generate { policy } => {
+                handle_pass_generate(&ctx, &policy.apply(&ctx.generator))?;
+            }
+            PassCommands::Rotate { path, clip, policy } => {
+                handle_pass_rotate(&ctx, &path, &policy, clip)?;
             }
         },
-        Commands::Show { path, show_all } => {
-            handle_show(&path, show_all, cli.key_path)?;
+        Commands::Show {
+            path,
+            show_all,
+            line,
+            field,
+            clip,
+        } => {
+            handle_show(&ctx, &path, show_all, line, field.as_deref(), clip)?;
+        }
+        Commands::List {
+            subfolder,
+            recipients,
+        } => {
+            handle_list(&ctx, subfolder.as_deref(), recipients)?;
         }
-        Commands::List { subfolder } => {
-            handle_list(subfolder.as_deref())?;
+        Commands::Find {
+            terms,
+            glob,
+            fuzzy,
+            flat,
+        } => {
+            handle_find(&ctx, &terms, glob, fuzzy, flat)?;
+        }
+        Commands::Grep {
+            pattern,
+            subfolder,
+            ignore_case,
+        } => {
+            handle_grep(&ctx, &pattern, subfolder.as_deref(), ignore_case)?;
         }
         Commands::Remove { path, recursive } => {
-            handle_remove(&path, recursive)?;
+            handle_remove(&ctx, &path, recursive)?;
         }
         Commands::Otp { command } => match command {
             OtpCommands::Add { path, uri } => {
-                handle_otp_add(&path, &uri, cli.key_path)?;
+                handle_otp_add(&ctx, &path, &uri)?;
             }
-            OtpCommands::Generate { path } => {
-                handle_otp_generate(&path, cli.key_path)?;
+            OtpCommands::Generate { path, clip } => {
+                handle_otp_generate(&ctx, &path, clip)?;
             }
         },
+        Commands::Mv { src, dst, force } => {
+            handle_mv(&ctx, &src, &dst, force)?;
+        }
+        Commands::Cp {
+            src,
+            dst,
+            recursive,
+            force,
+            to_profile,
+            to_store,
+        } => {
+            handle_cp(
+                &ctx,
+                &src,
+                &dst,
+                recursive,
+                force,
+                to_profile.as_deref(),
+                to_store.as_deref(),
+            )?;
+        }
         Commands::Edit { path } => {
-            handle_edit(&path, cli.key_path)?;
+            handle_edit(&ctx, &path)?;
+        }
+        Commands::Reencrypt { subfolder } => {
+            handle_reencrypt(&ctx, subfolder.as_deref())?;
         }
+        Commands::Recipients { command } => match command {
+            RecipientsCommands::Add {
+                recipient,
+                path,
+                reencrypt,
+            } => {
+                handle_recipients_add(&ctx, &recipient, path.as_deref(), reencrypt)?;
+            }
+            RecipientsCommands::Remove {
+                recipient,
+                path,
+                reencrypt,
+            } => {
+                handle_recipients_remove(&ctx, &recipient, path.as_deref(), reencrypt)?;
+            }
+            RecipientsCommands::List { path } => {
+                handle_recipients_list(&ctx, path.as_deref())?;
+            }
+        },
+        // Handled before the context is resolved.
+        Commands::Profile { .. } | Commands::ClipboardRestore { .. } => {}
+        Commands::Agent { command } => match command {
+            AgentCommands::Start {
+                ttl,
+                idle_timeout,
+                foreground,
+            } => {
+                handle_agent_start(&ctx, ttl, idle_timeout, foreground)?;
+            }
+            AgentCommands::Lock => {
+                handle_agent_lock()?;
+            }
+            AgentCommands::Status => {
+                handle_agent_status()?;
+            }
+            AgentCommands::Stop => {
+                handle_agent_stop()?;
+            }
+        },
     }
 
     Ok(())
@@ -0,0 +1,57 @@
+use std::io::{self, ErrorKind, Write};
+
+use eyre::Result;
+use tracing_subscriber::EnvFilter;
+
+/// Sets up diagnostics on stderr. `-q`/`-v` pick the level for zps; without
+/// them `RUST_LOG` is honored, falling back to `info`.
+pub fn init_logging(verbose: u8, quiet: bool) {
+    let level = if quiet {
+        Some("error")
+    } else {
+        match verbose {
+            0 => None,
+            1 => Some("debug"),
+            _ => Some("trace"),
+        }
+    };
+    let filter = match level {
+        Some(level) => EnvFilter::new(format!("warn,zps={}", level)),
+        None => {
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,zps=info"))
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+}
+
+/// Writes a secret to stdout exactly as stored, adding a final newline if it
+/// has none. A closed pipe (e.g. `zps show x | head -c0`) is not an error.
+pub fn print_secret(secret: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    let result = stdout
+        .write_all(secret.as_bytes())
+        .and_then(|_| {
+            if secret.ends_with('\n') {
+                Ok(())
+            } else {
+                stdout.write_all(b"\n")
+            }
+        })
+        .and_then(|_| stdout.flush());
+    match result {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+/// Shows a prompt on stderr, keeping stdout free for results.
+pub fn prompt(message: &str) -> Result<()> {
+    let mut stderr = io::stderr().lock();
+    write!(stderr, "{}", message)?;
+    stderr.flush()?;
+    Ok(())
+}
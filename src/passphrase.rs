@@ -0,0 +1,89 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use eyre::{Result, eyre};
+
+use crate::constants::MAX_PASSWORD_ATTEMPTS;
+
+const PASSPHRASE_PROMPT: &str = "Enter password for PGP key: ";
+
+/// Where the password of the PGP secret key comes from.
+#[derive(Debug, Clone)]
+pub enum PassphraseSource {
+    /// Ask on the terminal.
+    Prompt,
+    /// First line read from an inherited file descriptor (`--passphrase-fd`).
+    Fd(i32),
+    /// First line of a file (`ZPS_PASSPHRASE_FILE`).
+    File(PathBuf),
+    /// First line printed by an external program (`ZPS_ASKPASS`),
+    /// called with the prompt as its only argument like ssh-askpass.
+    Askpass(String),
+}
+
+impl PassphraseSource {
+    /// Picks the source from the configured options, in order of precedence:
+    /// file descriptor, file, askpass program, terminal prompt.
+    pub fn resolve(fd: Option<i32>, file: Option<PathBuf>, askpass: Option<String>) -> Self {
+        if let Some(fd) = fd {
+            PassphraseSource::Fd(fd)
+        } else if let Some(file) = file {
+            PassphraseSource::File(file)
+        } else if let Some(askpass) = askpass.filter(|a| !a.is_empty()) {
+            PassphraseSource::Askpass(askpass)
+        } else {
+            PassphraseSource::Prompt
+        }
+    }
+
+    /// How many times the password is asked for before giving up.
+    /// Only the terminal prompt can give a different answer on retry.
+    pub fn attempts(&self) -> u32 {
+        match self {
+            PassphraseSource::Prompt => MAX_PASSWORD_ATTEMPTS,
+            _ => 1,
+        }
+    }
+
+    /// Reads the password from this source.
+    pub fn read(&self) -> Result<String> {
+        match self {
+            PassphraseSource::Prompt => rpassword::prompt_password(PASSPHRASE_PROMPT)
+                .map_err(|e| eyre!("Failed to read password: {}", e)),
+            PassphraseSource::Fd(fd) => {
+                let content = fs::read_to_string(format!("/dev/fd/{}", fd))
+                    .map_err(|e| eyre!("Failed to read password from fd {}: {}", fd, e))?;
+                first_line(content, &format!("fd {}", fd))
+            }
+            PassphraseSource::File(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| eyre!("Failed to read password file {:?}: {}", path, e))?;
+                first_line(content, &format!("{:?}", path))
+            }
+            PassphraseSource::Askpass(program) => {
+                let output = Command::new(program)
+                    .arg(PASSPHRASE_PROMPT)
+                    .output()
+                    .map_err(|e| eyre!("Failed to run askpass program '{}': {}", program, e))?;
+                if !output.status.success() {
+                    return Err(eyre!(
+                        "Askpass program '{}' exited with {}.",
+                        program,
+                        output.status
+                    ));
+                }
+                let content = String::from_utf8(output.stdout)
+                    .map_err(|_| eyre!("Askpass program '{}' printed invalid UTF-8.", program))?;
+                first_line(content, &format!("askpass program '{}'", program))
+            }
+        }
+    }
+}
+
+/// Returns the first line of `content` without its line ending.
+fn first_line(content: String, origin: &str) -> Result<String> {
+    content
+        .lines()
+        .next()
+        .map(str::to_string)
+        .ok_or_else(|| eyre!("No password received from {}.", origin))
+}
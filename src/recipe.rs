@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use tracing::info;
+
+use crate::{generator::PasswordPolicy, gpg::Keyring};
+
+/// Recipe file applying to the folder containing it and every sub-folder
+/// without a recipe of its own.
+pub const RECIPE_FILE_NAME: &str = ".recipe";
+/// Extension of a recipe applying to a single entry, e.g. `bank/chase.recipe`.
+pub const RECIPE_EXTENSION: &str = "recipe";
+const ENCRYPTED_EXTENSION: &str = "gpg";
+
+/// Whether `path` is a recipe rather than a password entry.
+pub fn is_recipe_file(path: &Path) -> bool {
+    let path = match path.extension() {
+        Some(ext) if ext == ENCRYPTED_EXTENSION => path.with_extension(""),
+        _ => path.to_path_buf(),
+    };
+    path.file_name()
+        .is_some_and(|name| name == RECIPE_FILE_NAME)
+        || path.extension().is_some_and(|ext| ext == RECIPE_EXTENSION)
+}
+
+/// Returns the recipe at `path`, preferring the plain file over its encrypted sibling.
+fn existing_recipe(path: PathBuf) -> Option<PathBuf> {
+    let encrypted = PathBuf::from(format!("{}.{}", path.display(), ENCRYPTED_EXTENSION));
+    [path, encrypted].into_iter().find(|p| p.is_file())
+}
+
+/// Returns the paths of the plain and the encrypted sidecar recipe of the
+/// entry at `entry_file_path`, whether they exist or not.
+pub fn sidecar_recipe_paths(entry_file_path: &Path) -> [PathBuf; 2] {
+    let plain = entry_file_path.with_extension(RECIPE_EXTENSION);
+    let encrypted = PathBuf::from(format!("{}.{}", plain.display(), ENCRYPTED_EXTENSION));
+    [plain, encrypted]
+}
+
+/// Pairs every existing sidecar recipe of the entry at `src_entry_file_path`
+/// with where it goes when the entry is moved or copied to `dst_entry_file_path`.
+pub fn sidecar_recipe_targets(
+    src_entry_file_path: &Path,
+    dst_entry_file_path: &Path,
+) -> Vec<(PathBuf, PathBuf)> {
+    sidecar_recipe_paths(src_entry_file_path)
+        .into_iter()
+        .zip(sidecar_recipe_paths(dst_entry_file_path))
+        .filter(|(src, _)| src.is_file())
+        .collect()
+}
+
+/// Finds the recipe for the entry at `entry_file_path`: its own sidecar, or
+/// the nearest folder recipe up to and including the store root.
+pub fn find_recipe_file(store_path: &Path, entry_file_path: &Path) -> Option<PathBuf> {
+    if let Some(recipe) = sidecar_recipe_paths(entry_file_path)
+        .into_iter()
+        .find(|p| p.is_file())
+    {
+        return Some(recipe);
+    }
+    let mut current = entry_file_path.parent();
+    while let Some(folder) = current {
+        if let Some(recipe) = existing_recipe(folder.join(RECIPE_FILE_NAME)) {
+            return Some(recipe);
+        }
+        if folder == store_path {
+            break;
+        }
+        current = folder.parent().filter(|p| p.starts_with(store_path));
+    }
+    None
+}
+
+/// Parses a recipe, decrypting it first if it is a `.gpg` file.
+fn read_recipe(recipe_file: &Path, keyring: &Keyring) -> Result<toml::Table> {
+    let raw = if recipe_file
+        .extension()
+        .is_some_and(|ext| ext == ENCRYPTED_EXTENSION)
+    {
+        let decrypted = keyring
+            .decrypt(&fs::read(recipe_file)?)
+            .map_err(|e| eyre!("Failed to decrypt recipe {:?}: {}", recipe_file, e))?;
+        String::from_utf8(decrypted)
+            .map_err(|_| eyre!("Recipe {:?} is not valid UTF-8.", recipe_file))?
+    } else {
+        fs::read_to_string(recipe_file)
+            .map_err(|e| eyre!("Failed to read recipe {:?}: {}", recipe_file, e))?
+    };
+    raw.parse::<toml::Table>()
+        .map_err(|e| eyre!("Failed to parse recipe {:?}: {}", recipe_file, e))
+}
+
+/// Returns the generator policy for the entry at `entry_file_path`: `base`
+/// with the settings of the nearest recipe, if any, applied on top.
+/// Recipes use the same keys as the `[generator]` config section.
+pub fn resolve_policy(
+    store_path: &Path,
+    entry_file_path: &Path,
+    base: &PasswordPolicy,
+    keyring: &Keyring,
+) -> Result<PasswordPolicy> {
+    let Some(recipe_file) = find_recipe_file(store_path, entry_file_path) else {
+        return Ok(base.clone());
+    };
+    let recipe = read_recipe(&recipe_file, keyring)?;
+
+    let mut policy = toml::Table::try_from(base)?;
+    policy.extend(recipe);
+    let policy = policy
+        .try_into()
+        .map_err(|e| eyre!("Invalid recipe {:?}: {}", recipe_file, e))?;
+    info!("Using recipe {:?}", recipe_file);
+    Ok(policy)
+}
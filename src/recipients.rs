@@ -0,0 +1,259 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use eyre::{Result, eyre};
+use sequoia_openpgp::{Cert, parse::Parse};
+use tracing::{info, warn};
+
+use crate::gpg::{check_recipient_cert, load_and_validate_key_from_file};
+
+/// File listing the recipients entries are encrypted to. Applies to the folder
+/// containing it and every sub-folder without a recipients file of its own.
+pub const RECIPIENTS_FILE_NAME: &str = ".recipients";
+/// Directory inside the store holding the public certs of recipients,
+/// named `<FINGERPRINT>.asc`.
+pub const PUBLIC_KEYS_DIR_NAME: &str = ".public-keys";
+
+/// A single line of a recipients file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientSpec {
+    /// Fingerprint of a cert stored in the public keys directory.
+    Fingerprint(String),
+    /// Path, relative to the store root, of an armored cert file.
+    CertFile(PathBuf),
+}
+
+impl RecipientSpec {
+    pub fn parse(line: &str) -> Self {
+        let compact: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+        let is_fingerprint =
+            matches!(compact.len(), 40 | 64) && compact.chars().all(|c| c.is_ascii_hexdigit());
+        if is_fingerprint {
+            RecipientSpec::Fingerprint(compact.to_uppercase())
+        } else {
+            RecipientSpec::CertFile(PathBuf::from(line))
+        }
+    }
+
+    /// Returns the fingerprint this spec refers to, loading the cert file if needed.
+    pub fn fingerprint(&self, store_path: &Path) -> Option<String> {
+        match self {
+            RecipientSpec::Fingerprint(fpr) => Some(fpr.clone()),
+            RecipientSpec::CertFile(rel_path) => Cert::from_file(store_path.join(rel_path))
+                .ok()
+                .map(|cert| cert.fingerprint().to_hex()),
+        }
+    }
+}
+
+impl fmt::Display for RecipientSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecipientSpec::Fingerprint(fpr) => write!(f, "{}", fpr),
+            RecipientSpec::CertFile(rel_path) => write!(f, "{}", rel_path.display()),
+        }
+    }
+}
+
+/// Returns the path of the public cert for `fingerprint` inside the store.
+pub fn public_key_path(store_path: &Path, fingerprint: &str) -> PathBuf {
+    store_path
+        .join(PUBLIC_KEYS_DIR_NAME)
+        .join(format!("{}.asc", fingerprint.to_uppercase()))
+}
+
+/// Parses a recipients file line, returning `None` for blank lines and `#` comments.
+fn parse_recipients_line(line: &str) -> Option<RecipientSpec> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        None
+    } else {
+        Some(RecipientSpec::parse(line))
+    }
+}
+
+/// Parses a recipients file, skipping blank lines and `#` comments.
+pub fn read_recipients_file(recipients_file: &Path) -> Result<Vec<RecipientSpec>> {
+    let raw = fs::read_to_string(recipients_file).map_err(|e| {
+        eyre!(
+            "Failed to read recipients file {:?}: {}",
+            recipients_file,
+            e
+        )
+    })?;
+    Ok(raw.lines().filter_map(parse_recipients_line).collect())
+}
+
+/// Appends `spec` to a recipients file, creating the file if needed.
+/// Existing lines, comments included, are left as they are.
+pub fn append_recipient(recipients_file: &Path, spec: &RecipientSpec) -> Result<()> {
+    let mut content = if recipients_file.exists() {
+        fs::read_to_string(recipients_file)?
+    } else {
+        String::new()
+    };
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&format!("{}\n", spec));
+    fs::write(recipients_file, content)?;
+    Ok(())
+}
+
+/// Drops the recipient lines matching `remove` from a recipients file, keeping
+/// comments, blank lines and every other recipient line as they are.
+/// Returns the number of lines removed.
+pub fn remove_recipients(
+    recipients_file: &Path,
+    remove: impl Fn(&RecipientSpec) -> bool,
+) -> Result<usize> {
+    let raw = fs::read_to_string(recipients_file)?;
+    let mut removed = 0;
+    let mut content = String::with_capacity(raw.len());
+    for line in raw.split_inclusive('\n') {
+        if parse_recipients_line(line).is_some_and(|spec| remove(&spec)) {
+            removed += 1;
+        } else {
+            content.push_str(line);
+        }
+    }
+    fs::write(recipients_file, content)?;
+    Ok(removed)
+}
+
+/// Loads the cert for a single recipient and checks that it can still be encrypted to.
+/// A fingerprint matching `own_cert` resolves to it even if it was never exported to the store.
+fn load_recipient(store_path: &Path, spec: &RecipientSpec, own_cert: &Cert) -> Result<Cert> {
+    let cert = match spec {
+        RecipientSpec::Fingerprint(fpr) => {
+            let cert_path = public_key_path(store_path, fpr);
+            if cert_path.exists() {
+                let cert = load_and_validate_key_from_file(&cert_path)?;
+                let loaded = cert.fingerprint().to_hex();
+                if !loaded.eq_ignore_ascii_case(fpr) {
+                    return Err(eyre!(
+                        "Public key {:?} has fingerprint {}, not {}.",
+                        cert_path,
+                        loaded,
+                        fpr
+                    ));
+                }
+                Ok(cert)
+            } else if own_cert.fingerprint().to_hex().eq_ignore_ascii_case(fpr) {
+                Ok(own_cert.clone())
+            } else {
+                Err(eyre!(
+                    "No public key for recipient {} (expected at {:?}).",
+                    fpr,
+                    cert_path
+                ))
+            }
+        }
+        RecipientSpec::CertFile(rel_path) => {
+            if rel_path.is_absolute() || rel_path.components().any(|c| c.as_os_str() == "..") {
+                return Err(eyre!(
+                    "Recipient cert file must be a path inside the store: {:?}",
+                    rel_path
+                ));
+            }
+            load_and_validate_key_from_file(&store_path.join(rel_path))
+        }
+    }?;
+    check_recipient_cert(&cert).map_err(|e| {
+        eyre!(
+            "Cannot encrypt to recipient {}: {}. Remove it with 'zps recipients remove {}'.",
+            spec,
+            e,
+            spec
+        )
+    })?;
+    Ok(cert)
+}
+
+/// Loads and validates every recipient listed in `recipients_file`.
+pub fn load_recipients(
+    store_path: &Path,
+    recipients_file: &Path,
+    own_cert: &Cert,
+) -> Result<Vec<Cert>> {
+    let specs = read_recipients_file(recipients_file)?;
+    if specs.is_empty() {
+        return Err(eyre!(
+            "Recipients file {:?} does not list any recipients.",
+            recipients_file
+        ));
+    }
+
+    specs
+        .iter()
+        .map(|spec| load_recipient(store_path, spec, own_cert))
+        .collect()
+}
+
+/// Finds the recipients file that applies to `dir`: the nearest one in `dir`
+/// itself or any ancestor folder, up to and including the store root.
+pub fn find_recipients_file(store_path: &Path, dir: &Path) -> Option<PathBuf> {
+    let mut current = Some(dir);
+    while let Some(folder) = current {
+        let candidate = folder.join(RECIPIENTS_FILE_NAME);
+        if candidate.is_file() {
+            return Some(candidate);
+        }
+        if folder == store_path {
+            break;
+        }
+        current = folder.parent().filter(|p| p.starts_with(store_path));
+    }
+    None
+}
+
+/// Determines the certs the entry at `entry_file_path` is encrypted to.
+/// Uses the nearest recipients file above the entry, otherwise only `own_cert`.
+pub fn resolve_recipients(
+    store_path: &Path,
+    entry_file_path: &Path,
+    own_cert: &Cert,
+) -> Result<Vec<Cert>> {
+    let entry_dir = entry_file_path.parent().unwrap_or(store_path);
+    let Some(recipients_file) = find_recipients_file(store_path, entry_dir) else {
+        return Ok(vec![own_cert.clone()]);
+    };
+
+    let recipients = load_recipients(store_path, &recipients_file, own_cert)?;
+    info!(
+        "Using {} recipient(s) from {:?}",
+        recipients.len(),
+        recipients_file
+    );
+    if !recipients
+        .iter()
+        .any(|r| r.fingerprint() == own_cert.fingerprint())
+    {
+        warn!(
+            "Your key {} is not a recipient; you will not be able to decrypt this entry.",
+            own_cert.fingerprint().to_hex()
+        );
+    }
+
+    Ok(recipients)
+}
+
+/// Describes a recipient for display, using the primary user ID of its
+/// public cert when one can be found in the store.
+pub fn describe_recipient(store_path: &Path, spec: &RecipientSpec) -> String {
+    let cert_path = match spec {
+        RecipientSpec::Fingerprint(fpr) => public_key_path(store_path, fpr),
+        RecipientSpec::CertFile(rel_path) => store_path.join(rel_path),
+    };
+    let label = spec.to_string();
+
+    match Cert::from_file(&cert_path) {
+        Ok(cert) => match cert.userids().next() {
+            Some(uid) => format!("{} ({})", uid.userid(), label),
+            None => label,
+        },
+        Err(_) => label,
+    }
+}
@@ -5,12 +5,42 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
+use crate::recipe::is_recipe_file;
+
 pub const PASSWORD_STORE_DIR_NAME: &str = ".zps";
 pub const CONFIG_DIR_NAME: &str = ".config";
 pub const DEFAULT_KEY_FILE_NAME: &str = "key.pgp";
+/// Environment variables consulted, in order, for the store location.
+pub const STORE_DIR_ENV_VARS: [&str; 2] = ["ZPS_STORE_DIR", "PASSWORD_STORE_DIR"];
+
+/// Resolves the password store directory for this invocation.
+/// The `--store` flag wins, then `ZPS_STORE_DIR`/`PASSWORD_STORE_DIR`,
+/// then `store_path` from the config file,
+/// and finally the default from `get_password_store_path`.
+pub fn resolve_password_store_path(
+    cli_store: Option<&Path>,
+    config_store: Option<&Path>,
+) -> Result<PathBuf> {
+    if let Some(store) = cli_store {
+        return Ok(store.to_path_buf());
+    }
+
+    for var in STORE_DIR_ENV_VARS {
+        if let Ok(dir) = env::var(var)
+            && !dir.is_empty()
+        {
+            return Ok(PathBuf::from(dir));
+        }
+    }
+
+    if let Some(store) = config_store {
+        return Ok(store.to_path_buf());
+    }
+
+    get_password_store_path()
+}
 
-/// Returns the path to the password store directory.
-/// If not set, it defaults to `$HOME/.zps`.
+/// Returns the default path to the password store directory, `$HOME/.zps`.
 pub fn get_password_store_path() -> Result<PathBuf> {
     // Determine home directory based on OS
     let home_dir_str = env::var("HOME");
@@ -34,6 +64,28 @@ pub fn ensure_store_directory_exists(store_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Resolves the entry or folder name SRC gets when moved or copied to DST in `dst_store_path`.
+/// If DST is an existing folder or ends with a separator, SRC keeps its name inside it.
+pub fn resolve_destination_name(dst_store_path: &Path, src: &str, dst: &str) -> Result<String> {
+    // Validates DST the same way as entry names.
+    get_password_file_path(dst_store_path, dst)?;
+
+    if dst.ends_with(['/', '\\']) || dst_store_path.join(dst).is_dir() {
+        let src_name = src
+            .trim_end_matches(['/', '\\'])
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(src);
+        Ok(format!(
+            "{}/{}",
+            dst.trim_end_matches(['/', '\\']),
+            src_name
+        ))
+    } else {
+        Ok(dst.to_string())
+    }
+}
+
 /// Returns the path to a password file within the store.
 /// E.g., for "work/email", returns ~/.password-store/work/email.gpg
 pub fn get_password_file_path(store_path: &Path, entry_name: &str) -> Result<PathBuf> {
@@ -57,5 +109,12 @@ pub fn get_password_file_path(store_path: &Path, entry_name: &str) -> Result<Pat
         path.push(component);
     }
 
-    Ok(path.with_extension("gpg"))
+    let file_path = path.with_extension("gpg");
+    if is_recipe_file(&file_path) {
+        return Err(eyre!(
+            "Invalid password entry name (it would be read as a recipe): '{}'",
+            entry_name
+        ));
+    }
+    Ok(file_path)
 }
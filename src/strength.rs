@@ -0,0 +1,685 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::LazyLock,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, eyre};
+use regex::Regex;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    constants::DEFAULT_MIN_PASSWORD_SCORE,
+    generator::{PasswordPolicy, wordlist},
+};
+
+/// Very common passwords, most frequent first.
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "password",
+    "123456789",
+    "12345678",
+    "12345",
+    "qwerty",
+    "1234567",
+    "111111",
+    "123123",
+    "abc123",
+    "1234567890",
+    "password1",
+    "iloveyou",
+    "000000",
+    "qwerty123",
+    "1q2w3e4r",
+    "admin",
+    "qwertyuiop",
+    "654321",
+    "555555",
+    "lovely",
+    "7777777",
+    "welcome",
+    "888888",
+    "princess",
+    "dragon",
+    "123qwe",
+    "sunshine",
+    "666666",
+    "football",
+    "monkey",
+    "charlie",
+    "aa123456",
+    "donald",
+    "letmein",
+    "master",
+    "shadow",
+    "baseball",
+    "superman",
+    "trustno1",
+    "michael",
+    "jennifer",
+    "hunter",
+    "batman",
+    "starwars",
+    "whatever",
+    "freedom",
+    "computer",
+    "hello",
+    "cheese",
+    "ashley",
+    "soccer",
+    "jordan",
+    "access",
+    "mustang",
+    "harley",
+    "ranger",
+    "buster",
+    "thomas",
+    "tigger",
+    "robert",
+    "daniel",
+    "hannah",
+    "maggie",
+    "summer",
+    "love",
+    "pepper",
+    "ginger",
+    "secret",
+    "flower",
+    "matrix",
+    "hockey",
+    "george",
+    "andrew",
+    "joshua",
+    "internet",
+    "zaq12wsx",
+    "pokemon",
+    "liverpool",
+    "chelsea",
+    "arsenal",
+    "samsung",
+    "nicole",
+    "jessica",
+    "purple",
+    "orange",
+    "banana",
+    "cookie",
+    "login",
+    "changeme",
+    "test",
+    "guest",
+    "root",
+    "default",
+    "pass",
+    "zxcvbnm",
+    "asdfgh",
+    "qazwsx",
+    "1qaz2wsx",
+];
+
+static COMMON_PASSWORD_RANKS: LazyLock<HashMap<&str, usize>> = LazyLock::new(|| {
+    COMMON_PASSWORDS
+        .iter()
+        .enumerate()
+        .map(|(rank, word)| (*word, rank + 1))
+        .collect()
+});
+static WORDS: LazyLock<HashSet<&str>> = LazyLock::new(|| wordlist().into_iter().collect());
+static YEAR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"19\d\d|20\d\d").expect("valid year pattern"));
+/// The current year, from the system clock.
+static CURRENT_YEAR: LazyLock<i32> = LazyLock::new(|| {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    1970 + (elapsed.as_secs() / SECONDS_PER_YEAR) as i32
+});
+static SEPARATED_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(\d{1,4})[ ./_-](\d{1,2})[ ./_-](\d{1,4})").expect("valid date pattern")
+});
+
+/// Keyboard rows as (unshifted, shifted) characters.
+const KEYBOARD_ROWS: &[(&str, &str)] = &[
+    ("`1234567890-=", "~!@#$%^&*()_+"),
+    ("qwertyuiop[]\\", "QWERTYUIOP{}|"),
+    ("asdfghjkl;'", "ASDFGHJKL:\""),
+    ("zxcvbnm,./", "ZXCVBNM<>?"),
+];
+
+/// Years this far from now or closer are guessed as a group.
+const MIN_YEAR_SPACE: i32 = 20;
+/// Average length of a Gregorian year.
+const SECONDS_PER_YEAR: u64 = 31_556_952;
+/// Longer passwords are not analysed; they are out of reach either way.
+const MAX_ANALYSED_LENGTH: usize = 100;
+
+/// A guessable pattern found in a password.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pattern {
+    CommonPassword,
+    Word,
+    Sequence,
+    Repeat,
+    Keyboard,
+    Date,
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Pattern::CommonPassword => "it contains a very common password",
+            Pattern::Word => "it contains a dictionary word",
+            Pattern::Sequence => "it contains a sequence like 'abc' or '123'",
+            Pattern::Repeat => "it contains repeated characters",
+            Pattern::Keyboard => "it contains a run of adjacent keys",
+            Pattern::Date => "it contains a date or year",
+        })
+    }
+}
+
+/// Characters `start..end` of a password matching a pattern, with the
+/// number of guesses needed to find them as log10.
+struct Match {
+    start: usize,
+    end: usize,
+    log10_guesses: f64,
+    pattern: Pattern,
+}
+
+/// How hard a password is to guess, on zxcvbn's scale from 0 (trivial) to 4.
+#[derive(Debug, Clone)]
+pub struct Strength {
+    pub score: u8,
+    weakness: Option<Pattern>,
+}
+
+impl Strength {
+    fn from_guesses(log10_guesses: f64, weakness: Option<Pattern>) -> Self {
+        let score = match log10_guesses {
+            g if g < 3.0 => 0,
+            g if g < 6.0 => 1,
+            g if g < 8.0 => 2,
+            g if g < 10.0 => 3,
+            _ => 4,
+        };
+        Self { score, weakness }
+    }
+
+    /// Estimates the strength of a chosen password by finding the cheapest
+    /// way to guess it as a series of dictionary words, sequences, repeats,
+    /// keyboard runs, dates and brute-forced characters.
+    pub fn estimate(password: &str) -> Self {
+        let chars: Vec<char> = password.chars().collect();
+        if chars.len() > MAX_ANALYSED_LENGTH {
+            return Self::from_guesses(f64::INFINITY, None);
+        }
+        let (log10_guesses, weakness) = minimum_guesses(&chars);
+        Self::from_guesses(log10_guesses, weakness)
+    }
+
+    /// The strength of a random password with `bits` of entropy.
+    pub fn from_entropy(bits: f64) -> Self {
+        Self::from_guesses(bits * 2f64.log10(), None)
+    }
+}
+
+/// Minimum strength for passwords added to the store, read from `[strength]`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrengthConfig {
+    /// Lowest acceptable score, from 0 (accept anything) to 4.
+    pub min_score: u8,
+    /// Refuse entered passwords below the minimum instead of warning.
+    pub refuse_weak: bool,
+}
+
+impl Default for StrengthConfig {
+    fn default() -> Self {
+        Self {
+            min_score: DEFAULT_MIN_PASSWORD_SCORE,
+            refuse_weak: false,
+        }
+    }
+}
+
+impl StrengthConfig {
+    fn weakness(&self, subject: &str, strength: &Strength) -> Option<String> {
+        if strength.score >= self.min_score {
+            return None;
+        }
+        let reason = strength
+            .weakness
+            .map(|pattern| format!("; {}", pattern))
+            .unwrap_or_default();
+        Some(format!(
+            "{} is weak (score {}/4, minimum {}){}.",
+            subject, strength.score, self.min_score, reason
+        ))
+    }
+
+    /// Warns about an entered password below the minimum score, or refuses it
+    /// with `refuse_weak` unless `allow_weak` is set.
+    pub fn check(&self, subject: &str, password: &str, allow_weak: bool) -> Result<()> {
+        let Some(message) = self.weakness(subject, &Strength::estimate(password)) else {
+            return Ok(());
+        };
+        if self.refuse_weak && !allow_weak {
+            return Err(eyre!("{} Use --allow-weak to store it anyway.", message));
+        }
+        warn!("{}", message);
+        Ok(())
+    }
+
+    /// Reports the entropy of a password generated with `policy`. Generated
+    /// passwords below the minimum are only warned about, never refused.
+    pub fn report_generated(&self, policy: &PasswordPolicy) {
+        let bits = policy.entropy_bits();
+        info!("Generated password has about {:.0} bits of entropy.", bits);
+        if let Some(message) =
+            self.weakness("The generated password", &Strength::from_entropy(bits))
+        {
+            warn!("{}", message);
+        }
+    }
+}
+
+fn log10(guesses: f64) -> f64 {
+    guesses.max(1.0).log10()
+}
+
+/// Guesses for one character with nothing better to go on.
+fn bruteforce_log10(c: char) -> f64 {
+    if c.is_ascii_digit() {
+        log10(10.0)
+    } else if c.is_ascii_alphabetic() {
+        log10(26.0)
+    } else {
+        log10(33.0)
+    }
+}
+
+/// Returns the fewest guesses (as log10) needed to find `chars`, and the
+/// pattern covering most of it along that path.
+fn minimum_guesses(chars: &[char]) -> (f64, Option<Pattern>) {
+    let n = chars.len();
+    let mut matches = Vec::new();
+    dictionary_matches(chars, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    date_matches(chars, &mut matches);
+
+    // best[j] is the cheapest way to guess chars[..j].
+    let mut best = vec![0.0; n + 1];
+    let mut via: Vec<Option<usize>> = vec![None; n + 1];
+    for j in 1..=n {
+        best[j] = best[j - 1] + bruteforce_log10(chars[j - 1]);
+        for (k, m) in matches.iter().enumerate().filter(|(_, m)| m.end == j) {
+            let cost = best[m.start] + m.log10_guesses;
+            if cost < best[j] {
+                best[j] = cost;
+                via[j] = Some(k);
+            }
+        }
+    }
+
+    let mut weakness: Option<&Match> = None;
+    let mut j = n;
+    while j > 0 {
+        match via[j] {
+            Some(k) => {
+                let m = &matches[k];
+                if weakness.is_none_or(|w| m.end - m.start > w.end - w.start) {
+                    weakness = Some(m);
+                }
+                j = m.start;
+            }
+            None => j -= 1,
+        }
+    }
+    (best[n], weakness.map(|m| m.pattern))
+}
+
+/// Undoes common l33t substitutions, returning the number made.
+fn unleet(token: &str) -> (String, usize) {
+    let mut substitutions = 0;
+    let plain = token
+        .chars()
+        .map(|c| {
+            let plain = match c {
+                '4' | '@' => 'a',
+                '3' => 'e',
+                '1' | '!' => 'i',
+                '0' => 'o',
+                '$' | '5' => 's',
+                '7' => 't',
+                _ => return c,
+            };
+            substitutions += 1;
+            plain
+        })
+        .collect();
+    (plain, substitutions)
+}
+
+/// Extra guesses for the capitalization of a word, as log10.
+fn uppercase_log10(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        0.0
+    } else if lower == 0 || (upper == 1 && token[0].is_uppercase()) {
+        log10(2.0)
+    } else {
+        (upper.min(lower) + 1) as f64 * log10(2.0)
+    }
+}
+
+fn dictionary_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let lookup = |candidate: &str| {
+        COMMON_PASSWORD_RANKS
+            .get(candidate)
+            .map(|rank| (*rank as f64, Pattern::CommonPassword))
+            .or_else(|| {
+                WORDS
+                    .contains(candidate)
+                    .then_some((WORDS.len() as f64, Pattern::Word))
+            })
+    };
+
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len() {
+            let token = &chars[start..end];
+            let lower: String = token.iter().flat_map(|c| c.to_lowercase()).collect();
+            let (plain, substitutions) = unleet(&lower);
+            let mut candidates = vec![(lower.clone(), 0)];
+            if substitutions > 0 {
+                candidates.push((plain, substitutions));
+            }
+
+            for (candidate, substitutions) in candidates {
+                let reversed: String = candidate.chars().rev().collect();
+                let found = lookup(&candidate)
+                    .map(|found| (found, 1.0))
+                    .or_else(|| lookup(&reversed).map(|found| (found, 2.0)));
+                if let Some(((rank, pattern), reversal)) = found {
+                    let log10_guesses = log10(rank * reversal)
+                        + uppercase_log10(token)
+                        + substitutions as f64 * log10(2.0);
+                    matches.push(Match {
+                        start,
+                        end,
+                        log10_guesses,
+                        pattern,
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn char_class(c: char) -> u8 {
+    if c.is_ascii_lowercase() {
+        0
+    } else if c.is_ascii_uppercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else {
+        3
+    }
+}
+
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let steps_by = |a: char, b: char, delta: i64| {
+        char_class(a) == char_class(b) && char_class(a) != 3 && b as i64 - a as i64 == delta
+    };
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        if delta.abs() != 1 || !steps_by(chars[start], chars[start + 1], delta) {
+            start += 1;
+            continue;
+        }
+        let mut end = start + 2;
+        while end < chars.len() && steps_by(chars[end - 1], chars[end], delta) {
+            end += 1;
+        }
+        if end - start >= 3 {
+            let first = chars[start];
+            let base = if matches!(first, 'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9') {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start,
+                end,
+                log10_guesses: log10(base * direction * (end - start) as f64),
+                pattern: Pattern::Sequence,
+            });
+        }
+        start = end - 1;
+    }
+}
+
+fn repeat_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        // The shortest block repeated from here, e.g. "ab" in "ababab".
+        let repeat = (1..=(chars.len() - start) / 2).find_map(|block_len| {
+            let block = &chars[start..start + block_len];
+            let count = chars[start..]
+                .chunks(block_len)
+                .take_while(|chunk| *chunk == block)
+                .count();
+            (count >= 2 && block_len * count >= 3).then_some((block, count))
+        });
+        let Some((block, count)) = repeat else {
+            start += 1;
+            continue;
+        };
+        let (block_log10, _) = minimum_guesses(block);
+        let end = start + block.len() * count;
+        matches.push(Match {
+            start,
+            end,
+            log10_guesses: block_log10 + log10(count as f64),
+            pattern: Pattern::Repeat,
+        });
+        start = end;
+    }
+}
+
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let position = |c: char| {
+        KEYBOARD_ROWS
+            .iter()
+            .enumerate()
+            .find_map(|(row, (plain, shifted))| {
+                let col = plain.chars().position(|k| k == c).map(|col| (col, false));
+                col.or_else(|| shifted.chars().position(|k| k == c).map(|col| (col, true)))
+                    .map(|(col, shift)| (row, col as i64, shift))
+            })
+    };
+    let keys: Vec<_> = chars.iter().map(|&c| position(c)).collect();
+    let adjacent = |a: Option<(usize, i64, bool)>, b: Option<(usize, i64, bool)>, delta: i64| matches!((a, b), (Some(a), Some(b)) if a.0 == b.0 && b.1 - a.1 == delta);
+
+    let keyboard_size: f64 = KEYBOARD_ROWS.iter().map(|(row, _)| row.len() as f64).sum();
+    let mut start = 0;
+    while start + 2 < keys.len() {
+        let delta = match (keys[start], keys[start + 1]) {
+            (Some(a), Some(b)) if a.0 == b.0 && (b.1 - a.1).abs() == 1 => b.1 - a.1,
+            _ => {
+                start += 1;
+                continue;
+            }
+        };
+        let mut end = start + 2;
+        while end < keys.len() && adjacent(keys[end - 1], keys[end], delta) {
+            end += 1;
+        }
+        if end - start >= 3 {
+            let shifted = keys[start..end].iter().flatten().filter(|k| k.2).count();
+            let shift_log10 = if shifted == 0 || shifted == end - start {
+                0.0
+            } else {
+                log10(2.0)
+            };
+            matches.push(Match {
+                start,
+                end,
+                log10_guesses: log10(keyboard_size * 2.0 * (end - start) as f64) + shift_log10,
+                pattern: Pattern::Keyboard,
+            });
+        }
+        start = end - 1;
+    }
+}
+
+fn year_space(year: i32) -> f64 {
+    (year - *CURRENT_YEAR).abs().max(MIN_YEAR_SPACE) as f64
+}
+
+/// Reads a one- to four-digit year, taking two-digit years as 19xx or 20xx.
+fn parse_year(digits: &str) -> Option<i32> {
+    let year: i32 = digits.parse().ok()?;
+    match digits.len() {
+        2 if year > 50 => Some(1900 + year),
+        2 => Some(2000 + year),
+        4 if (1900..=2099).contains(&year) => Some(year),
+        _ => None,
+    }
+}
+
+/// Whether the three parts form a date in y/m/d, d/m/y or m/d/y order.
+fn date_year(parts: [&str; 3]) -> Option<i32> {
+    let is_day = |s: &str| s.parse::<u32>().is_ok_and(|d| (1..=31).contains(&d));
+    let is_month = |s: &str| s.parse::<u32>().is_ok_and(|m| (1..=12).contains(&m));
+    let [a, b, c] = parts;
+    if let Some(year) = parse_year(a)
+        && is_month(b)
+        && is_day(c)
+    {
+        return Some(year);
+    }
+    parse_year(c).filter(|_| (is_day(a) && is_month(b)) || (is_month(a) && is_day(b)))
+}
+
+fn date_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let text: String = chars.iter().collect();
+    let char_index = |byte: usize| text[..byte].chars().count();
+    let mut push = |start: usize, end: usize, guesses: f64| {
+        matches.push(Match {
+            start: char_index(start),
+            end: char_index(end),
+            log10_guesses: log10(guesses),
+            pattern: Pattern::Date,
+        });
+    };
+
+    for m in YEAR.find_iter(&text) {
+        if let Some(year) = parse_year(m.as_str()) {
+            push(m.start(), m.end(), year_space(year));
+        }
+    }
+
+    for caps in SEPARATED_DATE.captures_iter(&text) {
+        let whole = caps.get(0).expect("match has a whole group");
+        if let Some(year) = date_year([&caps[1], &caps[2], &caps[3]]) {
+            push(whole.start(), whole.end(), 365.0 * year_space(year) * 4.0);
+        }
+    }
+
+    // Unseparated dates such as 310199 or 19990131.
+    for len in [6, 8] {
+        for start in 0..chars.len().saturating_sub(len - 1) {
+            let token = &chars[start..start + len];
+            if !token.iter().all(char::is_ascii_digit) {
+                continue;
+            }
+            let token: String = token.iter().collect();
+            let year_len = len - 4;
+            let splits = [
+                [
+                    &token[..year_len],
+                    &token[year_len..year_len + 2],
+                    &token[year_len + 2..],
+                ],
+                [&token[..2], &token[2..4], &token[4..]],
+            ];
+            if let Some(year) = splits.into_iter().find_map(date_year) {
+                let byte_start = chars[..start].iter().map(|c| c.len_utf8()).sum::<usize>();
+                push(byte_start, byte_start + len, 365.0 * year_space(year));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_password_scores_zero() {
+        let strength = Strength::estimate("password");
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.weakness, Some(Pattern::CommonPassword));
+    }
+
+    #[test]
+    fn leet_variant_of_common_password_is_weak() {
+        assert!(Strength::estimate("P@ssw0rd").score <= 1);
+    }
+
+    #[test]
+    fn keyboard_run_is_weak() {
+        let strength = Strength::estimate("ghjkl;'");
+        assert!(strength.score <= 1);
+        assert_eq!(strength.weakness, Some(Pattern::Keyboard));
+    }
+
+    #[test]
+    fn sequence_is_weak() {
+        let strength = Strength::estimate("abcdefghij");
+        assert!(strength.score <= 1);
+        assert_eq!(strength.weakness, Some(Pattern::Sequence));
+    }
+
+    #[test]
+    fn date_is_weak() {
+        let strength = Strength::estimate("14.07.1989");
+        assert!(strength.score <= 1);
+        assert_eq!(strength.weakness, Some(Pattern::Date));
+    }
+
+    #[test]
+    fn recent_year_is_weak() {
+        let year = CURRENT_YEAR.to_string();
+        assert_eq!(Strength::estimate(&year).weakness, Some(Pattern::Date));
+    }
+
+    #[test]
+    fn long_random_password_scores_four() {
+        let strength = Strength::estimate("k7#Qz!vR2$mW9pLx&T4n");
+        assert_eq!(strength.score, 4);
+        assert_eq!(strength.weakness, None);
+    }
+
+    #[test]
+    fn non_ascii_input_does_not_panic() {
+        for password in [
+            "pässwörd",
+            "日本語のパスワード",
+            "🔑🔑🔑🔑",
+            "ß1990-01-01é",
+            "åbc",
+        ] {
+            Strength::estimate(password);
+        }
+    }
+}
@@ -11,13 +11,13 @@ use tracing::info;
 
 use crate::{
     gpg::{generate_key_with_password, load_and_validate_key_from_file},
+    output::prompt,
     store::{CONFIG_DIR_NAME, DEFAULT_KEY_FILE_NAME},
 };
 
 /// Helper for getting trimmed user input from stdin.
 fn get_trimmed_user_input(prompt_message: &str) -> Result<String> {
-    info!("{}", prompt_message);
-    std::io::stdout().flush()?;
+    prompt(prompt_message)?;
     let mut input = String::new();
     std::io::stdin().read_line(&mut input)?;
     Ok(input.trim().to_string())
@@ -108,11 +108,25 @@ pub fn determine_key(home_dir: &Path, key_path: Option<String>) -> Result<(Cert,
     }
 }
 
-/// Opens the given initial_content in a text editor determined by the EDITOR
-/// environment variable (or a fallback like "vi").
+/// Expands a leading `~` in the given path to the user's home directory.
+/// Paths without a leading `~`, or when HOME is not set, are returned unchanged.
+pub fn expand_tilde(path: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~")
+        && let Ok(home) = std::env::var("HOME")
+    {
+        return PathBuf::from(home).join(rest);
+    }
+    path.to_path_buf()
+}
+
+/// Opens the given initial_content in a text editor: the configured editor if any,
+/// otherwise the one named by the EDITOR environment variable (or a fallback like "vi").
 /// Returns the modified content as a String.
-pub fn edit_content_in_editor(initial_content: &str) -> Result<String> {
-    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+pub fn edit_content_in_editor(initial_content: &str, editor: Option<&str>) -> Result<String> {
+    let editor = match editor {
+        Some(e) => e.to_string(),
+        None => std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string()),
+    };
 
     let mut temp_file = NamedTempFile::new()?;
 